@@ -1,4 +1,8 @@
+use super::local::LocalMatch;
 use super::mine_image::MineImage;
+use super::replay::ReplayViewer;
+use crate::settings::{Settings, Theme};
+use minesboomer_protocol::{BoardGeneration, BotStrength, CellMark, ClientMessage, GameDifficulty, LobbyPlayer, MarkedCell, PlayerStats, PlayerSummary, Replay, BOARD_DEALING, BOARD_SIDE_RANGE, BOTS, CAPABILITIES, CELL_MARKS, MAX_CHAT_LENGTH, PROTOCOL_VERSION, SEAT_RANGE, SEEDS};
 use minesweeper_multiplayer::serializables::*;
 use minesweeper_multiplayer::*;
 
@@ -7,11 +11,34 @@ use egui::{Button, Color32, Label, RichText, TextStyle, Ui, WidgetText};
 use futures::channel::mpsc::UnboundedSender;
 use tokio_tungstenite::tungstenite::protocol::Message;
 
+/// Colours of the marks placed by each seat, in seat order.
+const PLAYER_COLORS: [Color32; 6] = [
+    Color32::from_rgb(70, 130, 220),
+    Color32::from_rgb(220, 140, 40),
+    Color32::from_rgb(70, 170, 90),
+    Color32::from_rgb(170, 80, 200),
+    Color32::from_rgb(40, 170, 170),
+    Color32::from_rgb(200, 70, 120),
+];
+
+/// How long a cell has to be held down to mark it, for touch screens without a secondary button.
+const LONG_PRESS_SECS: f64 = 0.5;
+
+/// A mark on an uncleared cell and the player who put it there.
+struct PlacedMark {
+    coordinates: Point,
+    mark: CellMark,
+    player_id: String,
+}
+
 #[derive(Clone)]
 pub struct OpenGame {
     pub name: String,
     pub difficulty: String,
+    pub players: usize,
+    pub seats: usize,
     pub game_id: String,
+    pub is_running: bool,
 }
 
 pub struct MinesBoomer {
@@ -20,14 +47,57 @@ pub struct MinesBoomer {
     sender: UnboundedSender<Message>,
     is_active: bool,
     show_games_list: Option<Vec<OpenGame>>,
-    pub waiting_for_enemy: bool,
+    pub in_lobby: bool,
     show_game_name_popup: bool,
     game_creation_view: GameCreationView,
     game_name: String,
+    game_difficulty: GameDifficulty,
+    game_seats: usize,
+    game_seed: Option<u64>,
+    game_generation: BoardGeneration,
+    bot_strength: BotStrength,
+    /// Board of the games that start right away, against a bot or on this device.
+    quick_difficulty: GameDifficulty,
+    lobby_view: LobbyView,
+    chat_view: ChatView,
+    rematch_requests: Vec<String>,
+    has_requested_rematch: bool,
+    /// Set by the server's `GameOver`, the whole board is shown from then on.
+    is_game_over: bool,
+    winner: Option<String>,
+    /// Flags and question marks on the board, shared by every player of the game.
+    marks: Vec<PlacedMark>,
+    /// Cell held down long enough to be marked, so releasing it doesn't select it too.
+    long_pressed: Option<Point>,
+    /// Seed the server dealt the current board from, to play it again elsewhere.
+    seed: Option<u64>,
+    stats: Vec<PlayerStats>,
+    replay_viewer: Option<ReplayViewer>,
+    replay_path: String,
+    /// Set while two people play on this machine, the server isn't involved then.
+    local_match: Option<LocalMatch>,
+    /// Seconds left in the current turn and the full turn length, when the server clocks turns.
+    turn_timer: Option<(u64, u64)>,
+    connect_sender: UnboundedSender<String>,
+    settings: Settings,
+    show_connection_screen: bool,
+    connection_view: ConnectionView,
+    is_connecting: bool,
+    /// Capabilities of the server this client is connected to, features it lacks are hidden.
+    server_capabilities: Vec<String>,
+    connection_error: Option<String>,
+    move_rejection: Option<String>,
+    is_spectating: bool,
+    players: Vec<PlayerSummary>,
+    session_token: Option<String>,
+    reconnect_status: Option<String>,
+    /// Round trip time to the server, as last measured by its pings.
+    latency_ms: Option<u64>,
+    notice: Option<String>,
 }
 
 impl MinesBoomer {
-    pub fn new(sender: UnboundedSender<Message>, game: Multiplayer) -> Self {
+    pub fn new(sender: UnboundedSender<Message>, connect_sender: UnboundedSender<String>, game: Multiplayer, settings: Settings) -> Self {
         // Customize egui here with cc.egui_ctx.set_fonts and cc.egui_ctx.set_visuals.
         // Restore app state using cc.storage (requires the "persistence" feature).
         // Use the cc.gl (a glow::Context) to create graphics shaders and buffers that you can use
@@ -39,24 +109,87 @@ impl MinesBoomer {
             sender,
             is_active: false,
             show_games_list: None,
-            waiting_for_enemy: false,
+            in_lobby: false,
             show_game_name_popup: false,
             game_creation_view: GameCreationView::default(),
             game_name: "".to_owned(),
+            game_difficulty: GameDifficulty::default(),
+            game_seats: *SEAT_RANGE.start(),
+            game_seed: None,
+            game_generation: BoardGeneration::default(),
+            bot_strength: BotStrength::default(),
+            quick_difficulty: GameDifficulty::default(),
+            lobby_view: LobbyView::default(),
+            chat_view: ChatView::default(),
+            rematch_requests: vec![],
+            has_requested_rematch: false,
+            is_game_over: false,
+            winner: None,
+            marks: vec![],
+            long_pressed: None,
+            seed: None,
+            stats: vec![],
+            replay_viewer: None,
+            replay_path: "".to_owned(),
+            local_match: None,
+            turn_timer: None,
+            connect_sender,
+            connection_view: ConnectionView::new(&settings),
+            settings,
+            show_connection_screen: true,
+            is_connecting: false,
+            server_capabilities: vec![],
+            connection_error: None,
+            move_rejection: None,
+            is_spectating: false,
+            players: vec![],
+            session_token: None,
+            reconnect_status: None,
+            latency_ms: None,
+            notice: None,
         }
     }
 
     fn draw_cell(&mut self, cell: &Cell, ui: &mut Ui) {
-        let color = get_color_for_cell(cell);
-        let text = get_text_for_cell(cell);
+        let mark = self.mark_at(cell.coordinates).filter(|_| !cell.cleared);
+        let color = get_color_for_cell(cell, mark.map(|(_, color)| color));
+        let text = get_text_for_cell(cell, mark.map(|(mark, _)| mark));
 
+        let size = self.settings.cell_size;
         if cell.is_mine() && cell.cleared {
-            self.mine.ui(ui);
-        } else if ui.add_sized([50., 50.], Button::new(text).fill(color)).clicked() {
+            self.mine.ui(ui, size);
+            return;
+        }
+        let response = ui.add_sized([size, size], Button::new(text).fill(color));
+        let is_held = response.is_pointer_button_down_on();
+        let held_secs = ui.input().pointer.press_start_time().map_or(0., |start| ui.input().time - start);
+        if is_held && held_secs >= LONG_PRESS_SECS && self.long_pressed != Some(cell.coordinates) {
+            self.long_pressed = Some(cell.coordinates);
+            self.on_cell_marked(cell, mark.map(|(mark, _)| mark));
+        } else if response.clicked() && self.long_pressed != Some(cell.coordinates) {
             self.on_cell_tapped(cell);
+        } else if response.secondary_clicked() {
+            self.on_cell_marked(cell, mark.map(|(mark, _)| mark));
+        }
+        if is_held {
+            // Nothing else wakes the UI up while a finger rests on the cell.
+            ui.ctx().request_repaint();
+        } else if self.long_pressed == Some(cell.coordinates) && !ui.input().pointer.any_down() {
+            self.long_pressed = None;
         }
     }
 
+    /// The mark on the cell at `coordinates`, in the colour of the player who placed it.
+    fn mark_at(&self, coordinates: Point) -> Option<(CellMark, Color32)> {
+        let placed = self.marks.iter().find(|placed| placed.coordinates == coordinates)?;
+        let seat = self.players.iter().position(|player| player.id == placed.player_id).unwrap_or(0);
+        Some((placed.mark, PLAYER_COLORS[seat % PLAYER_COLORS.len()]))
+    }
+
+    fn flag_count(&self) -> u32 {
+        self.marks.iter().filter(|placed| placed.mark == CellMark::Flag).count() as u32
+    }
+
     fn get_copied_cell_at(&self, coordinates: Point) -> Option<Cell> {
         self.game.get_board().cell_at(coordinates).copied()
     }
@@ -67,9 +200,12 @@ impl MinesBoomer {
             for x in 0..dimentions.width {
                 ui.vertical(|ui| {
                     for y in 0..dimentions.height {
-                        let Some(cell) = self.get_copied_cell_at(Point { x, y }) else {
+                        let Some(mut cell) = self.get_copied_cell_at(Point { x, y }) else {
                             continue;
                         };
+                        if self.is_game_over {
+                            cell.cleared = true;
+                        }
                         self.draw_cell(&cell, ui);
                     }
                 });
@@ -78,36 +214,141 @@ impl MinesBoomer {
     }
 
     fn draw_gui(&mut self, ui: &mut Ui) {
-        if let Some(winner) = self.game.winner() {
-            ui.vertical_centered_justified(|ui| {
-                ui.heading("WINNER!");
-                ui.heading(winner.name.to_string());
-            });
+        if self.is_game_over {
+            self.draw_game_over(ui);
             return;
         }
 
-        let remining_mines = self.game.game.remaining_mines();
-        let mines_to_win = self.game.remaining_to_win();
-        let winning = self.game.player_winning();
+        let remining_mines = self.remaining_mines();
+        let flag_count = self.flag_count();
+        let winning = self.leader();
+        let active_player = self.players.iter().find(|player| player.is_active).map(|player| player.name.clone());
         let is_active = self.is_active;
+        let move_rejection = self.move_rejection.as_ref();
+        let is_spectating = self.is_spectating;
+        let players = &self.players;
+        let notice = self.notice.as_ref();
+        let turn_timer = self.turn_timer;
+        let is_local = self.local_match.is_some();
+        let seed = self.seed;
+        let mut quit_clicked = false;
 
         ui.vertical_centered_justified(|ui| {
-            if is_active {
+            if is_spectating {
+                ui.heading("Spectating");
+            } else if is_active {
                 ui.heading("Is YOUR tourn!");
-            } else {
-                ui.heading("Your enemy is playing");
+            } else if let Some(active_player) = active_player {
+                ui.heading(format!("{} is playing", active_player));
+            }
+            if let Some(rejection) = move_rejection {
+                ui.colored_label(Color32::LIGHT_RED, rejection);
+            }
+            if let Some(notice) = notice {
+                ui.colored_label(Color32::YELLOW, notice);
+            }
+            if let Some((remaining_secs, turn_secs)) = turn_timer {
+                let progress = remaining_secs as f32 / turn_secs.max(1) as f32;
+                ui.add(egui::ProgressBar::new(progress).text(format!("{}s left", remaining_secs)));
+            }
+            for player in players {
+                let turn_marker = if player.is_active { "▶ " } else { "" };
+                let forfeit_marker = if player.has_forfeited { " (forfeited)" } else { "" };
+                ui.label(format!("{}{}: {} mines{}", turn_marker, player.name, player.score, forfeit_marker));
             }
             // ui.heading(current_player);
-            ui.label(format!("Mines left: {}", remining_mines));
-            if mines_to_win <= 5 {
-                let Some(winning) = winning else {
-                    return
-                };
-                ui.separator();
-                ui.label(format!("{} is winning!", winning.name));
-                ui.label(format!("{} mines to go", mines_to_win));
+            // Flags are only guesses, the count can't go below zero even when players over-flag.
+            match flag_count {
+                0 => ui.label(format!("Mines left: {}", remining_mines)),
+                _ => ui.label(format!("Mines left: {} ({} flagged)", remining_mines.saturating_sub(flag_count), flag_count)),
+            };
+            if remining_mines <= 5 {
+                if let Some(winning) = winning {
+                    ui.separator();
+                    ui.label(format!("{} is winning!", winning.name));
+                }
+            }
+            if let Some(seed) = seed {
+                ui.label(format!("Board seed: {}", seed));
+            }
+            if is_local {
+                ui.add_space(10.);
+                quit_clicked = ui.button("Quit local game").clicked();
             }
         });
+        if quit_clicked {
+            self.leave_local_game();
+        }
+    }
+
+    fn draw_game_over(&mut self, ui: &mut Ui) {
+        let is_local = self.local_match.is_some();
+        let mut rematch_clicked = false;
+        let mut leave_clicked = false;
+        ui.vertical_centered_justified(|ui| {
+            match &self.winner {
+                Some(winner) => {
+                    ui.heading("WINNER!");
+                    ui.heading(winner);
+                }
+                None => {
+                    ui.heading("DRAW!");
+                }
+            }
+            ui.separator();
+            ui.label("Series:");
+            for player in &self.players {
+                ui.label(format!("{}: {} won", player.name, player.wins));
+            }
+            if !self.rematch_requests.is_empty() {
+                ui.label(format!("{} want a rematch", self.rematch_requests.join(", ")));
+            }
+            if let Some(notice) = &self.notice {
+                ui.colored_label(Color32::YELLOW, notice);
+            }
+            ui.add_space(10.);
+            if is_local {
+                rematch_clicked = ui.button("Play again").clicked();
+                leave_clicked = ui.button("Quit local game").clicked();
+                return;
+            }
+            if ui.button("Save replay").clicked() {
+                self.send_replay_request_message();
+            }
+            if !self.is_spectating {
+                rematch_clicked = ui.add_enabled(!self.has_requested_rematch, Button::new("Rematch")).clicked();
+            }
+            leave_clicked = ui.button("Back to lobby").clicked();
+        });
+        if is_local {
+            if rematch_clicked {
+                self.deal_local_game();
+            }
+            if leave_clicked {
+                self.leave_local_game();
+            }
+            return;
+        }
+        if rematch_clicked {
+            self.has_requested_rematch = true;
+            self.send_rematch_message();
+        }
+        if leave_clicked {
+            self.send_leave_game_message();
+            self.present_open_games_menu(vec![]);
+            self.request_open_games();
+        }
+    }
+
+    fn remaining_mines(&self) -> u32 {
+        self.game.game.remaining_mines()
+    }
+
+    /// The player with the most mines, unless several share the lead.
+    fn leader(&self) -> Option<&PlayerSummary> {
+        let leader = self.players.iter().max_by_key(|player| player.score)?;
+        let is_tied = self.players.iter().filter(|player| player.score == leader.score).count() > 1;
+        (!is_tied).then_some(leader)
     }
 
     fn draw_game_list(&mut self, ui: &mut Ui, game_list: &[OpenGame]) {
@@ -117,22 +358,52 @@ impl MinesBoomer {
             ui.add(title_label);
             ui.separator();
             ui.add_space(10.);
+            if let Some(notice) = &self.notice {
+                ui.colored_label(Color32::YELLOW, notice);
+            }
             ui.label("Chose a game to join or create a new one.");
             ui.add_space(10.);
             ui.vertical_centered(|ui| {
                 if ui.button("New game").clicked() {
+                    self.is_spectating = false;
                     self.show_game_name_popup = true;
                 }
+                ui.add_space(5.);
+                let is_valid = self.draw_quick_difficulty(ui);
+                if self.server_supports(BOTS) {
+                    if ui.add_enabled(is_valid, Button::new("Play vs. bot")).clicked() {
+                        self.play_against_bot();
+                    }
+                    bot_strength_ui(ui, &mut self.bot_strength);
+                    ui.add_space(5.);
+                }
+                if ui.add_enabled(is_valid, Button::new("Local game")).clicked() {
+                    self.start_local_game();
+                }
                 if !game_list.is_empty() {
                     ui.add_space(10.);
                     ui.label("Current games:");
                     ui.add_space(10.);
                 }
                 game_list.iter().for_each(|game| {
-                    if ui.add_sized([150., 30.], Button::new(&game.name)).clicked() {
-                        self.send_join_game_message(&game.game_id);
-                    }
+                    ui.horizontal(|ui| {
+                        ui.label(&game.difficulty);
+                        ui.label(format!("{}/{}", game.players, game.seats));
+                        if game.is_running {
+                            if ui.add_sized([150., 30.], Button::new(format!("Watch {}", game.name))).clicked() {
+                                self.is_spectating = true;
+                                self.send_spectate_game_message(&game.game_id);
+                            }
+                        } else if ui.add_sized([150., 30.], Button::new(&game.name)).clicked() {
+                            self.is_spectating = false;
+                            self.send_join_game_message(&game.game_id);
+                        }
+                    });
                 });
+                ui.add_space(20.);
+                self.draw_leaderboard(ui);
+                ui.add_space(10.);
+                self.draw_replay_loader(ui);
             });
         });
 
@@ -141,67 +412,490 @@ impl MinesBoomer {
         }
     }
 
+    fn draw_replay_loader(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Replay file:");
+            ui.text_edit_singleline(&mut self.replay_path);
+            if ui.add_enabled(!self.replay_path.trim().is_empty(), Button::new("Watch")).clicked() {
+                match ReplayViewer::load(self.replay_path.trim().as_ref()) {
+                    Ok(viewer) => {
+                        self.notice = None;
+                        self.is_game_over = false;
+                        self.marks.clear();
+                        self.game = viewer.game();
+                        self.replay_viewer = Some(viewer);
+                    }
+                    Err(err) => self.notice = Some(err),
+                }
+            }
+        });
+    }
+
+    fn draw_replay_controls(&mut self, ui: &mut Ui) {
+        let Some(viewer) = &mut self.replay_viewer else {
+            return;
+        };
+        let mut has_moved = false;
+        let mut close_clicked = false;
+        ui.vertical_centered_justified(|ui| {
+            ui.heading("Replay");
+            ui.label(viewer.players().join(" vs "));
+            if let Some(seed) = viewer.seed() {
+                ui.label(format!("Board seed: {}", seed));
+            }
+            ui.label(format!("Move {}/{}", viewer.position(), viewer.move_count()));
+            if let Some(record) = viewer.last_move() {
+                let outcome = if record.found_mine { "found a mine" } else { "cleared a cell" };
+                ui.label(format!("{} {} at {:.1}s", record.player, outcome, record.elapsed_ms as f32 / 1000.));
+            }
+            ui.horizontal(|ui| {
+                if ui.add_enabled(viewer.position() > 0, Button::new("◀ Back")).clicked() {
+                    viewer.step_back();
+                    has_moved = true;
+                }
+                if ui.add_enabled(viewer.position() < viewer.move_count(), Button::new("Forward ▶")).clicked() {
+                    viewer.step_forward();
+                    has_moved = true;
+                }
+            });
+            close_clicked = ui.button("Close replay").clicked();
+        });
+        if has_moved {
+            self.game = viewer.game();
+        }
+        if close_clicked {
+            self.replay_viewer = None;
+        }
+    }
+
+    fn draw_leaderboard(&mut self, ui: &mut Ui) {
+        let leaderboard = egui::CollapsingHeader::new("Leaderboard").show(ui, |ui| {
+            if self.stats.is_empty() {
+                ui.label("No games played yet.");
+                return;
+            }
+            egui::Grid::new("leaderboard").striped(true).show(ui, |ui| {
+                for heading in ["Player", "Games", "Wins", "Losses", "Mines found"] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for player in &self.stats {
+                    ui.label(&player.name);
+                    ui.label(player.games.to_string());
+                    ui.label(player.wins.to_string());
+                    ui.label(player.losses.to_string());
+                    ui.label(player.mines_found.to_string());
+                    ui.end_row();
+                }
+            });
+        });
+        // Fresh numbers every time the leaderboard is toggled.
+        if leaderboard.header_response.clicked() {
+            self.request_stats();
+        }
+    }
+
     fn show_game_creation_window(&mut self, ui: &Ui) {
-        let closed = self.game_creation_view.show(ui.ctx(), |name| {
+        let closed = self.game_creation_view.show(ui.ctx(), &self.server_capabilities, |name, difficulty, seats, seed, generation| {
             self.game_name = name;
+            self.game_difficulty = difficulty;
+            self.game_seats = seats;
+            self.game_seed = seed;
+            self.game_generation = generation;
             self.show_game_name_popup = false;
         });
 
         if !self.game_name.is_empty() {
-            self.send_create_new_game_message(&self.game_name);
+            self.send_create_new_game_message(&self.game_name, self.game_difficulty, self.game_seats, self.game_seed, Some(self.game_generation));
             self.game_name = "".to_owned();
         }
 
         self.show_game_name_popup = !closed;
     }
 
-    fn draw_waiting_screen(&self, ui: &mut Ui) {
-        ui.heading("Waiting for your enemy to connect...");
+    fn draw_lobby(&mut self, ui: &mut Ui) {
+        let can_add_bots = self.server_supports(BOTS);
+        let action = ui.vertical(|ui| self.lobby_view.ui(ui, can_add_bots)).inner;
+        match action {
+            Some(LobbyAction::SetReady(is_ready)) => self.send_message(ClientMessage::SetReady { is_ready }),
+            Some(LobbyAction::Kick(player_id)) => self.send_message(ClientMessage::KickPlayer { player_id }),
+            Some(LobbyAction::AddBot(strength)) => self.send_message(ClientMessage::AddBot { strength }),
+            Some(LobbyAction::ChangeDifficulty(difficulty)) => self.send_message(ClientMessage::ChangeDifficulty { difficulty }),
+            None => {}
+        }
+    }
+
+    fn draw_chat(&mut self, ui: &mut Ui) {
+        if let Some(text) = self.chat_view.ui(ui) {
+            self.send_message(ClientMessage::Chat { text });
+        }
+    }
+
+    fn draw_connection_screen(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            let title = WidgetText::RichText(RichText::new("MinesBooMer!").size(50.));
+            ui.add(Label::new(title));
+            ui.separator();
+            ui.add_space(10.);
+            if self.is_connecting {
+                ui.label(format!("Connecting to {}...", self.settings.server_url));
+                return;
+            }
+            if let Some(error) = &self.connection_error {
+                ui.colored_label(Color32::LIGHT_RED, error);
+                ui.add_space(10.);
+            }
+            if let Some((server_url, nickname)) = self.connection_view.ui(ui) {
+                self.connect(server_url, nickname);
+            }
+            ui.add_space(20.);
+            ui.label("Or play with someone next to you, no server needed:");
+            let is_valid = self.draw_quick_difficulty(ui);
+            if ui.add_enabled(is_valid, Button::new("Local game")).clicked() {
+                self.start_local_game();
+            }
+        });
+    }
+
+    /// Picks the board of the games that skip the creation window, returning whether it's a valid one.
+    fn draw_quick_difficulty(&mut self, ui: &mut Ui) -> bool {
+        difficulty_ui(ui, &mut self.quick_difficulty);
+        match self.quick_difficulty.validate() {
+            Ok(()) => true,
+            Err(reason) => {
+                ui.colored_label(Color32::LIGHT_RED, reason);
+                false
+            }
+        }
+    }
+
+    fn draw_settings_bar(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Theme:");
+            let previous_theme = self.settings.theme;
+            ui.selectable_value(&mut self.settings.theme, Theme::Dark, "Dark");
+            ui.selectable_value(&mut self.settings.theme, Theme::Light, "Light");
+            if self.settings.theme != previous_theme {
+                ui.ctx().set_visuals(self.settings.visuals());
+            }
+            ui.separator();
+            ui.label("Cell size:");
+            ui.add(egui::Slider::new(&mut self.settings.cell_size, 30.0..=80.0));
+            if self.is_in_game() {
+                ui.separator();
+                let chat_label = match self.chat_view.unread {
+                    0 => "Chat".to_owned(),
+                    unread => format!("Chat ({})", unread),
+                };
+                ui.toggle_value(&mut self.chat_view.is_open, chat_label);
+            }
+            if let Some(status) = &self.reconnect_status {
+                ui.separator();
+                ui.colored_label(Color32::YELLOW, status);
+            } else if let Some(latency_ms) = self.latency_ms {
+                ui.separator();
+                ui.label(format!("Ping: {} ms", latency_ms));
+            }
+        });
+    }
+
+    pub fn save_settings(&self) {
+        self.settings.save();
+    }
+
+    fn connect(&mut self, server_url: String, nickname: String) {
+        self.settings.server_url = server_url;
+        self.settings.nickname = nickname;
+        self.settings.save();
+        self.connection_error = None;
+        self.session_token = None;
+        self.is_connecting = true;
+        self.connect_sender.unbounded_send(self.settings.server_url.clone()).unwrap();
     }
 
     fn on_cell_tapped(&mut self, cell: &Cell) {
-        if !self.is_active {
+        if let Some(local_match) = &mut self.local_match {
+            if !self.is_game_over {
+                local_match.select(&mut self.game, cell.coordinates);
+                self.update_local_match();
+            }
             return;
         }
-        if self.game.winner().is_none() {
-            self.game.player_selected(cell.coordinates);
-
+        if self.is_spectating || !self.is_active || self.replay_viewer.is_some() {
+            return;
+        }
+        // The move is applied once the server confirms it.
+        if !self.is_game_over {
             self.send_selected_message(cell);
         }
     }
 
+    fn on_cell_marked(&mut self, cell: &Cell, mark: Option<CellMark>) {
+        if let Some(local_match) = &self.local_match {
+            let player_id = local_match.active_player_id();
+            if !self.is_game_over {
+                self.set_cell_mark(cell.coordinates, CellMark::next(mark), player_id);
+            }
+            return;
+        }
+        if self.is_spectating || self.is_game_over || self.replay_viewer.is_some() || !self.server_supports(CELL_MARKS) {
+            return;
+        }
+        // Like selections, the mark shows once the server relays it.
+        self.send_mark_message(cell, CellMark::next(mark));
+    }
+
     pub fn set_is_active(&mut self, is_active: bool) {
         self.is_active = is_active;
     }
 
     pub fn remote_player_selected(&mut self, coordinates: Point) {
+        self.move_rejection = None;
         self.game.player_selected(coordinates);
+        self.forget_cleared_marks();
+    }
+
+    /// Drops the marks of the cells the last selection cleared, like the server does with its own.
+    fn forget_cleared_marks(&mut self) {
+        let board = self.game.get_board();
+        self.marks.retain(|placed| board.cell_at(placed.coordinates).is_some_and(|cell| !cell.cleared));
+    }
+
+    fn start_local_game(&mut self) {
+        self.local_match = Some(LocalMatch::new(self.quick_difficulty));
+        self.deal_local_game();
+    }
+
+    fn deal_local_game(&mut self) {
+        let Some(local_match) = &mut self.local_match else {
+            return;
+        };
+        let game = local_match.deal();
+        let difficulty = local_match.get_difficulty();
+        let players = local_match.summaries();
+        self.start_game(game.get_board().clone(), difficulty);
+        self.set_players(players);
+        // Nobody is "you" on a shared screen, the heading names whose turn it is.
+        self.is_active = false;
+        self.is_spectating = false;
+        self.in_lobby = false;
+        self.show_games_list = None;
+        self.show_connection_screen = false;
+    }
+
+    /// Shows the local match after a move, ending it once it has a winner.
+    fn update_local_match(&mut self) {
+        let Some(local_match) = &self.local_match else {
+            return;
+        };
+        let players = local_match.summaries();
+        let winner = local_match.winner(&self.game);
+        let is_over = local_match.is_over(&self.game);
+        self.set_players(players);
+        self.forget_cleared_marks();
+        if is_over {
+            self.present_game_over(winner, self.game.get_board().clone());
+        }
+    }
+
+    /// Goes back to where the local game was started from: the open games when connected, the connection screen otherwise.
+    fn leave_local_game(&mut self) {
+        self.local_match = None;
+        self.is_game_over = false;
+        if self.session_token.is_some() {
+            self.present_open_games_menu(vec![]);
+            self.request_open_games();
+        } else {
+            self.show_connection_screen = true;
+        }
+    }
+
+    pub fn set_cell_mark(&mut self, coordinates: Point, mark: Option<CellMark>, player_id: String) {
+        self.marks.retain(|placed| placed.coordinates != coordinates);
+        if let Some(mark) = mark {
+            self.marks.push(PlacedMark { coordinates, mark, player_id });
+        }
+    }
+
+    /// Replaces every mark with the ones of a game snapshot.
+    pub fn set_marks(&mut self, marks: Vec<MarkedCell>) {
+        self.marks = marks
+            .into_iter()
+            .map(|marked| PlacedMark {
+                coordinates: marked.coordinates.into(),
+                mark: marked.mark,
+                player_id: marked.player_id,
+            })
+            .collect();
+    }
+
+    pub fn set_players(&mut self, players: Vec<PlayerSummary>) {
+        self.players = players;
+    }
+
+    pub fn set_lobby(&mut self, players: Vec<LobbyPlayer>, seats: usize, difficulty: GameDifficulty, player_id: String) {
+        self.notice = None;
+        self.lobby_view.update(players, seats, difficulty, player_id);
+    }
+
+    pub fn present_move_rejection(&mut self, reason: String) {
+        self.move_rejection = Some(reason);
+    }
+
+    pub fn start_game(&mut self, board: Board, difficulty: GameDifficulty) {
+        self.game = difficulty.new_game(["Player 1", "Player 2"]);
+        self.game.game.board = board;
+        self.notice = None;
+        self.rematch_requests.clear();
+        self.has_requested_rematch = false;
+        self.is_game_over = false;
+        self.winner = None;
+        self.marks.clear();
+        self.seed = None;
+        self.turn_timer = None;
+    }
+
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
     }
 
+    /// Swaps the board of the running game for the one dealt around its first selection.
     pub fn set_board(&mut self, board: Board) {
-        self.game.game.board = board
+        self.game.game.board = board;
+    }
+
+    pub fn set_turn_timer(&mut self, remaining_secs: u64, turn_secs: u64) {
+        self.turn_timer = Some((remaining_secs, turn_secs));
+    }
+
+    pub fn present_game_over(&mut self, winner: Option<String>, board: Board) {
+        self.game.game.board = board;
+        self.winner = winner;
+        self.is_game_over = true;
+        self.turn_timer = None;
+        self.move_rejection = None;
+    }
+
+    pub fn set_rematch_requests(&mut self, requested_by: Vec<String>) {
+        self.rematch_requests = requested_by;
     }
 
     pub fn present_open_games_menu(&mut self, games: Vec<OpenGame>) {
+        self.chat_view.clear();
+        self.show_connection_screen = false;
+        self.is_connecting = false;
         self.show_games_list = Some(games);
     }
 
     pub fn close_open_games_menu(&mut self) {
         self.show_games_list = None;
     }
+
+    pub fn present_connection_error(&mut self, error: String) {
+        self.show_connection_screen = true;
+        self.is_connecting = false;
+        self.connection_error = Some(error);
+    }
+
+    pub fn connection_closed(&mut self, error: Option<String>) {
+        self.show_games_list = None;
+        self.in_lobby = false;
+        self.session_token = None;
+        self.reconnect_status = None;
+        self.latency_ms = None;
+        if self.connection_error.is_none() {
+            let error = error.unwrap_or_else(|| "The connection to the server was closed.".to_owned());
+            self.present_connection_error(error);
+        }
+    }
+
+    pub fn set_server_capabilities(&mut self, capabilities: Vec<String>) {
+        self.server_capabilities = capabilities;
+    }
+
+    fn server_supports(&self, capability: &str) -> bool {
+        self.server_capabilities.iter().any(|supported| supported == capability)
+    }
+
+    pub fn set_session_token(&mut self, session_token: String) {
+        self.session_token = Some(session_token);
+        self.reconnect_status = None;
+    }
+
+    /// Whether a dropped connection should be retried to resume the current session.
+    pub fn can_resume(&self) -> bool {
+        self.session_token.is_some() && self.connection_error.is_none()
+    }
+
+    pub fn set_latency(&mut self, latency_ms: u64) {
+        self.latency_ms = Some(latency_ms);
+    }
+
+    pub fn present_reconnecting(&mut self, attempt: u32, max_attempts: u32) {
+        self.reconnect_status = Some(format!("Connection lost, reconnecting ({}/{})...", attempt, max_attempts));
+    }
+
+    pub fn save_replay(&mut self, replay: Replay) {
+        let notice = match ReplayViewer::save(&replay) {
+            Ok(path) => format!("Replay saved to {}", path.display()),
+            Err(err) => err,
+        };
+        self.notice = Some(notice);
+    }
+
+    pub fn set_stats(&mut self, stats: Vec<PlayerStats>) {
+        self.stats = stats;
+    }
+
+    pub fn present_notice(&mut self, notice: Option<String>) {
+        self.notice = notice;
+    }
+
+    pub fn receive_chat(&mut self, from: String, text: String) {
+        self.chat_view.push(from, text);
+    }
+
+    /// Whether the player sits in a lobby or a game, where the chat is available.
+    fn is_in_game(&self) -> bool {
+        !self.show_connection_screen && self.show_games_list.is_none() && self.local_match.is_none()
+    }
+
+    /// Whether the user asked to connect, so the networking thread is needed.
+    pub fn is_connecting(&self) -> bool {
+        self.is_connecting
+    }
 }
 
 impl eframe::App for MinesBoomer {
-    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let window_size = frame.info().window_info.size;
+        self.settings.dimensions = Some((window_size.x, window_size.y));
+
+        egui::TopBottomPanel::top("settings_bar").show(ctx, |ui| {
+            self.draw_settings_bar(ui);
+        });
+        let show_chat = self.is_in_game() && self.chat_view.is_open;
+        egui::SidePanel::right("chat_panel").resizable(true).default_width(220.).show_animated(ctx, show_chat, |ui| {
+            self.draw_chat(ui);
+        });
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal_top(|ui| {
+                if self.show_connection_screen {
+                    self.draw_connection_screen(ui);
+                    return;
+                }
+                if self.replay_viewer.is_some() {
+                    self.draw_board(ui);
+                    self.draw_replay_controls(ui);
+                    return;
+                }
                 if self.show_games_list.is_some() {
                     let list = self.show_games_list.as_ref().unwrap();
                     self.draw_game_list(ui, &list.clone());
                     return;
                 }
-                if self.waiting_for_enemy {
-                    self.draw_waiting_screen(ui);
+                if self.in_lobby {
+                    self.draw_lobby(ui);
                     return;
                 }
                 self.draw_board(ui);
@@ -216,52 +910,112 @@ impl eframe::App for MinesBoomer {
 impl MinesBoomer {
     pub fn request_user_id(&self) {
         println!("<- Sending player identification");
-        let name = "Player".to_owned();
-        let message = IdentificationMessage::new(name);
-
-        self.send_message(message.to_json_string());
+        let message = ClientMessage::Identify {
+            name: self.settings.nickname.clone(),
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: CAPABILITIES.iter().map(|capability| capability.to_string()).collect(),
+            session_token: self.session_token.clone(),
+        };
+        self.send_message(message);
     }
 
     pub fn request_open_games(&self) {
         println!("<- Sending games request");
-        let message = SimpleMessage::new("games_request");
-        self.send_message(message.to_json_string());
+        self.send_message(ClientMessage::GamesRequest);
+    }
+
+    fn send_replay_request_message(&self) {
+        println!("<- Sending replay request");
+        self.send_message(ClientMessage::ReplayRequest);
+    }
+
+    fn request_stats(&self) {
+        println!("<- Sending stats request");
+        self.send_message(ClientMessage::StatsRequest);
     }
 
     pub fn send_selected_message(&self, cell: &Cell) {
         println!("<- Sending cell selected");
-        let serializable: SerializablePoint = cell.coordinates.into();
-        let message = CellSelectedMessage::new(serializable, false);
-        self.send_message(message.to_json_string());
+        let coordinates: SerializablePoint = cell.coordinates.into();
+        self.send_message(ClientMessage::CellSelected { coordinates });
+    }
+
+    /// Creates a two seat game with a bot in the other seat, which starts right away.
+    fn play_against_bot(&mut self) {
+        println!("<- Sending create game against a bot");
+        self.is_spectating = false;
+        let name = format!("{} vs. bot", self.settings.nickname);
+        self.send_create_new_game_message(&name, self.quick_difficulty, 2, None, None);
+        self.send_message(ClientMessage::AddBot { strength: self.bot_strength });
+        self.send_message(ClientMessage::SetReady { is_ready: true });
+    }
+
+    fn send_mark_message(&self, cell: &Cell, mark: Option<CellMark>) {
+        println!("<- Sending mark cell");
+        let coordinates: SerializablePoint = cell.coordinates.into();
+        self.send_message(ClientMessage::MarkCell { coordinates, mark });
     }
 
     fn send_join_game_message(&self, game_id: impl Into<String>) {
         println!("<- Sending joing game");
-        let message = JoinGameMessage::new(game_id, "Great Player");
-        self.send_message(message.to_json_string());
+        let message = ClientMessage::JoinGame {
+            game_id: game_id.into(),
+        };
+        self.send_message(message);
     }
 
-    fn send_message(&self, json: String) {
-        self.sender.unbounded_send(Message::Text(json)).unwrap();
+    pub fn disconnect(&self) {
+        println!("<- Closing connection");
+        self.sender.unbounded_send(Message::Close(None)).unwrap();
+    }
+
+    fn send_rematch_message(&self) {
+        println!("<- Sending rematch request");
+        self.send_message(ClientMessage::RequestRematch);
+    }
+
+    fn send_leave_game_message(&self) {
+        println!("<- Sending leave game");
+        self.send_message(ClientMessage::LeaveGame);
+    }
+
+    fn send_spectate_game_message(&self, game_id: impl Into<String>) {
+        println!("<- Sending spectate game");
+        let message = ClientMessage::SpectateGame {
+            game_id: game_id.into(),
+        };
+        self.send_message(message);
+    }
+
+    fn send_message(&self, message: ClientMessage) {
+        self.sender.unbounded_send(Message::Text(message.to_json_string())).unwrap();
     }
 }
 
-fn get_color_for_cell(cell: &Cell) -> Color32 {
+fn get_color_for_cell(cell: &Cell, mark_color: Option<Color32>) -> Color32 {
     if cell.is_mine() && cell.cleared {
         Color32::from_rgba_premultiplied(150, 29, 27, 100)
     } else if cell.cleared {
         Color32::GRAY
+    } else if let Some(mark_color) = mark_color {
+        mark_color
     } else {
         Color32::from_gray(55)
     }
 }
 
-fn get_text_for_cell(cell: &Cell) -> WidgetText {
+fn get_text_for_cell(cell: &Cell, mark: Option<CellMark>) -> WidgetText {
     let text = |cell: &Cell| {
         if cell.cleared && !cell.is_mine() && cell.number > 0 {
             cell.number.to_string()
-        } else {
+        } else if cell.cleared {
             "".to_string()
+        } else {
+            match mark {
+                Some(CellMark::Flag) => "⚑".to_string(),
+                Some(CellMark::Question) => "?".to_string(),
+                None => "".to_string(),
+            }
         }
     };
 
@@ -269,37 +1023,94 @@ fn get_text_for_cell(cell: &Cell) -> WidgetText {
 }
 
 pub trait MessageSender {
-    fn send_create_new_game_message(&self, name: &str);
+    fn send_create_new_game_message(&self, name: &str, difficulty: GameDifficulty, seats: usize, seed: Option<u64>, generation: Option<BoardGeneration>);
 }
 
 impl MessageSender for MinesBoomer {
-    fn send_create_new_game_message(&self, game_name: &str) {
+    fn send_create_new_game_message(&self, game_name: &str, difficulty: GameDifficulty, seats: usize, seed: Option<u64>, generation: Option<BoardGeneration>) {
         println!("<- Sending create new game");
-        let message = CreateGameMessage::new(game_name, Difficulty::Easy);
-        self.send_message(message.to_json_string());
+        let message = ClientMessage::CreateGame {
+            name: game_name.to_owned(),
+            difficulty: Some(difficulty),
+            seats: Some(seats),
+            seed,
+            generation,
+        };
+        self.send_message(message);
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Debug)]
 struct GameCreationView {
     name: String,
+    difficulty: GameDifficulty,
+    seats: usize,
+    /// Typed by the host, left empty for a random board.
+    seed: String,
+    generation: BoardGeneration,
+}
+
+impl Default for GameCreationView {
+    fn default() -> Self {
+        GameCreationView {
+            name: "".to_owned(),
+            difficulty: GameDifficulty::default(),
+            seats: *SEAT_RANGE.start(),
+            seed: "".to_owned(),
+            generation: BoardGeneration::default(),
+        }
+    }
 }
 
 impl GameCreationView {
-    fn show(&mut self, ctx: &egui::Context, on_send: impl FnMut(String)) -> bool {
+    fn show(&mut self, ctx: &egui::Context, capabilities: &[String], on_send: impl FnMut(String, GameDifficulty, usize, Option<u64>, BoardGeneration)) -> bool {
         let mut closed = false;
         egui::Window::new("New Game").resizable(true).default_width(280.0).show(ctx, |ui| {
-            self.ui(ui, on_send, &mut closed);
+            self.ui(ui, capabilities, on_send, &mut closed);
         });
         closed
     }
 
-    fn ui(&mut self, ui: &mut egui::Ui, mut on_send: impl FnMut(String), close: &mut bool) {
+    fn ui(&mut self, ui: &mut egui::Ui, capabilities: &[String], mut on_send: impl FnMut(String, GameDifficulty, usize, Option<u64>, BoardGeneration), close: &mut bool) {
+        let supports = |capability: &str| capabilities.iter().any(|supported| supported == capability);
         ui.label("The name for the new game:");
         ui.text_edit_singleline(&mut self.name);
 
-        if ui.button(format!("Create game: '{}'", self.name)).clicked() {
-            on_send(self.name.clone());
+        ui.add_space(10.);
+        difficulty_ui(ui, &mut self.difficulty);
+        let validation = self.difficulty.validate();
+        if let Err(reason) = &validation {
+            ui.colored_label(Color32::LIGHT_RED, reason);
+        }
+        ui.add_space(10.);
+        ui.horizontal(|ui| {
+            ui.label("Players:");
+            ui.add(egui::Slider::new(&mut self.seats, SEAT_RANGE));
+        });
+        ui.add_space(10.);
+        // Older servers would silently ignore these.
+        if supports(BOARD_DEALING) {
+            generation_ui(ui, &mut self.generation);
+            ui.add_space(10.);
+        }
+        if supports(SEEDS) {
+            ui.horizontal(|ui| {
+                ui.label("Seed:");
+                ui.text_edit_singleline(&mut self.seed).on_hover_text("Leave empty for a random board");
+            });
+        }
+        let seed = match self.seed.trim() {
+            "" => Ok(None),
+            seed => seed.parse::<u64>().map(Some),
+        };
+        if seed.is_err() {
+            ui.colored_label(Color32::LIGHT_RED, "The seed must be a whole positive number");
+        }
+        ui.add_space(10.);
+
+        let create_button = Button::new(format!("Create game: '{}'", self.name));
+        if ui.add_enabled(validation.is_ok() && seed.is_ok(), create_button).clicked() {
+            on_send(self.name.clone(), self.difficulty, self.seats, seed.ok().flatten(), self.generation);
         }
 
         if ui.button("Cancel").clicked() {
@@ -309,3 +1120,215 @@ impl GameCreationView {
         }
     }
 }
+
+fn bot_strength_ui(ui: &mut egui::Ui, strength: &mut BotStrength) {
+    ui.horizontal(|ui| {
+        ui.label("Bot:");
+        for option in [BotStrength::Easy, BotStrength::Normal, BotStrength::Expert] {
+            ui.radio_value(strength, option, option.to_string());
+        }
+    });
+}
+
+fn generation_ui(ui: &mut egui::Ui, generation: &mut BoardGeneration) {
+    ui.label("Board:");
+    ui.horizontal(|ui| {
+        for option in [BoardGeneration::Classic, BoardGeneration::SafeStart, BoardGeneration::NoGuess] {
+            ui.radio_value(generation, option, option.to_string());
+        }
+    });
+}
+
+fn difficulty_ui(ui: &mut egui::Ui, difficulty: &mut GameDifficulty) {
+    ui.label("Difficulty:");
+    ui.horizontal(|ui| {
+        ui.radio_value(difficulty, GameDifficulty::Easy, "Easy");
+        ui.radio_value(difficulty, GameDifficulty::Medium, "Medium");
+        ui.radio_value(difficulty, GameDifficulty::Hard, "Hard");
+        let is_custom = matches!(difficulty, GameDifficulty::Custom { .. });
+        if ui.radio(is_custom, "Custom").clicked() && !is_custom {
+            *difficulty = GameDifficulty::Custom { width: 10, height: 10, mines: 15 };
+        }
+    });
+
+    if let GameDifficulty::Custom { width, height, mines } = difficulty {
+        ui.horizontal(|ui| {
+            ui.label("Width:");
+            ui.add(egui::DragValue::new(width).clamp_range(BOARD_SIDE_RANGE));
+            ui.label("Height:");
+            ui.add(egui::DragValue::new(height).clamp_range(BOARD_SIDE_RANGE));
+            ui.label("Mines:");
+            ui.add(egui::DragValue::new(mines).clamp_range(1..=*width * *height));
+        });
+    }
+}
+
+enum LobbyAction {
+    SetReady(bool),
+    Kick(String),
+    AddBot(BotStrength),
+    ChangeDifficulty(GameDifficulty),
+}
+
+#[derive(Default, Debug)]
+struct LobbyView {
+    players: Vec<LobbyPlayer>,
+    seats: usize,
+    difficulty: GameDifficulty,
+    player_id: String,
+    /// The host's edit of the difficulty, sent once applied.
+    pending_difficulty: GameDifficulty,
+    bot_strength: BotStrength,
+}
+
+impl LobbyView {
+    fn update(&mut self, players: Vec<LobbyPlayer>, seats: usize, difficulty: GameDifficulty, player_id: String) {
+        if self.difficulty != difficulty || self.player_id != player_id {
+            self.pending_difficulty = difficulty;
+        }
+        self.players = players;
+        self.seats = seats;
+        self.difficulty = difficulty;
+        self.player_id = player_id;
+    }
+
+    fn me(&self) -> Option<&LobbyPlayer> {
+        self.players.iter().find(|player| player.id == self.player_id)
+    }
+
+    /// Returns what the user asked for, if anything.
+    fn ui(&mut self, ui: &mut egui::Ui, can_add_bots: bool) -> Option<LobbyAction> {
+        let mut action = None;
+        let is_host = self.me().is_some_and(|me| me.is_host);
+        let is_ready = self.me().is_some_and(|me| me.is_ready);
+
+        ui.heading(format!("Lobby ({}/{} players)", self.players.len(), self.seats));
+        ui.label(format!("Board: {}", self.difficulty));
+        ui.add_space(10.);
+        for player in &self.players {
+            ui.horizontal(|ui| {
+                let ready_marker = if player.is_ready { "✔" } else { "…" };
+                let host_marker = if player.is_host { " (host)" } else { "" };
+                ui.label(format!("{} {}{}", ready_marker, player.name, host_marker));
+                if is_host && player.id != self.player_id && ui.small_button("Kick").clicked() {
+                    action = Some(LobbyAction::Kick(player.id.clone()));
+                }
+            });
+        }
+        ui.add_space(10.);
+
+        if is_host {
+            difficulty_ui(ui, &mut self.pending_difficulty);
+            let validation = self.pending_difficulty.validate();
+            if let Err(reason) = &validation {
+                ui.colored_label(Color32::LIGHT_RED, reason);
+            }
+            let can_apply = validation.is_ok() && self.pending_difficulty != self.difficulty;
+            if ui.add_enabled(can_apply, Button::new("Change difficulty")).clicked() {
+                action = Some(LobbyAction::ChangeDifficulty(self.pending_difficulty));
+            }
+            ui.add_space(10.);
+            if can_add_bots && self.players.len() < self.seats {
+                bot_strength_ui(ui, &mut self.bot_strength);
+                if ui.button("Add bot").clicked() {
+                    action = Some(LobbyAction::AddBot(self.bot_strength));
+                }
+                ui.add_space(10.);
+            }
+        }
+
+        let ready_label = if is_ready { "Not ready" } else { "Ready" };
+        if ui.button(ready_label).clicked() {
+            action = Some(LobbyAction::SetReady(!is_ready));
+        }
+        if self.players.len() < 2 {
+            ui.label("Waiting for more players to join...");
+        } else {
+            ui.label("The game starts once everyone is ready.");
+        }
+        action
+    }
+}
+
+#[derive(Default, Debug)]
+struct ChatView {
+    messages: Vec<(String, String)>,
+    draft: String,
+    is_open: bool,
+    /// Messages received while the panel was collapsed.
+    unread: usize,
+}
+
+impl ChatView {
+    fn push(&mut self, from: String, text: String) {
+        if !self.is_open {
+            self.unread += 1;
+        }
+        self.messages.push((from, text));
+    }
+
+    fn clear(&mut self) {
+        self.messages.clear();
+        self.unread = 0;
+    }
+
+    /// Returns the message to send once the user submits the draft.
+    fn ui(&mut self, ui: &mut egui::Ui) -> Option<String> {
+        self.unread = 0;
+        ui.heading("Chat");
+        ui.separator();
+        egui::ScrollArea::vertical().stick_to_bottom(true).max_height(ui.available_height() - 40.).show(ui, |ui| {
+            for (from, text) in &self.messages {
+                ui.label(RichText::new(format!("{}:", from)).strong());
+                ui.label(text);
+            }
+        });
+        ui.separator();
+
+        let mut text = None;
+        ui.horizontal(|ui| {
+            let input = ui.add(egui::TextEdit::singleline(&mut self.draft).desired_width(150.));
+            // Text edits can't limit their length themselves, long drafts are cut back after each edit.
+            if let Some((end, _)) = self.draft.char_indices().nth(MAX_CHAT_LENGTH) {
+                self.draft.truncate(end);
+            }
+            let submitted = input.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+            if (ui.button("Send").clicked() || submitted) && !self.draft.trim().is_empty() {
+                text = Some(self.draft.trim().to_owned());
+                self.draft.clear();
+                input.request_focus();
+            }
+        });
+        text
+    }
+}
+
+#[derive(Debug)]
+struct ConnectionView {
+    server_url: String,
+    nickname: String,
+}
+
+impl ConnectionView {
+    fn new(settings: &Settings) -> Self {
+        ConnectionView {
+            server_url: settings.server_url.clone(),
+            nickname: settings.nickname.clone(),
+        }
+    }
+
+    /// Returns the server URL and nickname once the user asks to connect.
+    fn ui(&mut self, ui: &mut egui::Ui) -> Option<(String, String)> {
+        ui.label("Server:");
+        ui.text_edit_singleline(&mut self.server_url);
+        ui.label("Nickname:");
+        ui.text_edit_singleline(&mut self.nickname);
+        ui.add_space(10.);
+
+        let can_connect = !self.server_url.trim().is_empty() && !self.nickname.trim().is_empty();
+        if ui.add_enabled(can_connect, Button::new("Connect")).clicked() {
+            return Some((self.server_url.trim().to_owned(), self.nickname.trim().to_owned()));
+        }
+        None
+    }
+}
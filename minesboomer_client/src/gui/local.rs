@@ -0,0 +1,94 @@
+use minesboomer_protocol::{GameDifficulty, PlayerSummary};
+use minesweeper_multiplayer::{Multiplayer, Point};
+
+/// Names of the two seats of a local game.
+const LOCAL_PLAYERS: [&str; 2] = ["Player 1", "Player 2"];
+
+/// Two people taking turns on one machine, without a server.
+/// Turns and scores follow the server's rules: finding a mine scores and keeps the turn, anything else passes it.
+pub struct LocalMatch {
+    difficulty: GameDifficulty,
+    scores: [u32; 2],
+    wins: [u32; 2],
+    turn: usize,
+    /// Who plays first, alternating between matches.
+    starter: usize,
+}
+
+impl LocalMatch {
+    pub fn new(difficulty: GameDifficulty) -> Self {
+        LocalMatch {
+            difficulty,
+            scores: [0; 2],
+            wins: [0; 2],
+            turn: 0,
+            starter: 0,
+        }
+    }
+
+    /// A fresh board for the next match of the series.
+    pub fn deal(&mut self) -> Multiplayer {
+        self.scores = [0; 2];
+        self.turn = self.starter;
+        self.starter = (self.starter + 1) % LOCAL_PLAYERS.len();
+        self.difficulty.new_game(LOCAL_PLAYERS)
+    }
+
+    pub fn get_difficulty(&self) -> GameDifficulty {
+        self.difficulty
+    }
+
+    /// Plays the active player's selection of `coordinates` on `game`.
+    pub fn select(&mut self, game: &mut Multiplayer, coordinates: Point) {
+        let Some(cell) = game.get_board().cell_at(coordinates).copied() else {
+            return;
+        };
+        if cell.cleared {
+            return;
+        }
+        game.player_selected(coordinates);
+        if cell.is_mine() {
+            self.scores[self.turn] += 1;
+        } else {
+            self.turn = (self.turn + 1) % LOCAL_PLAYERS.len();
+        }
+        if let Some(winner) = self.winner_index(game) {
+            self.wins[winner] += 1;
+        }
+    }
+
+    /// The player the other can't catch up with anymore, if any.
+    pub fn winner(&self, game: &Multiplayer) -> Option<String> {
+        self.winner_index(game).map(|index| LOCAL_PLAYERS[index].to_owned())
+    }
+
+    pub fn is_over(&self, game: &Multiplayer) -> bool {
+        self.winner_index(game).is_some() || game.game.remaining_mines() == 0
+    }
+
+    fn winner_index(&self, game: &Multiplayer) -> Option<usize> {
+        let remaining_mines = game.game.remaining_mines();
+        let (leader, trailer) = if self.scores[0] >= self.scores[1] { (0, 1) } else { (1, 0) };
+        (self.scores[leader] > self.scores[trailer] + remaining_mines).then_some(leader)
+    }
+
+    /// The id of the player whose turn it is, as used in the summaries.
+    pub fn active_player_id(&self) -> String {
+        self.turn.to_string()
+    }
+
+    pub fn summaries(&self) -> Vec<PlayerSummary> {
+        LOCAL_PLAYERS
+            .iter()
+            .enumerate()
+            .map(|(index, name)| PlayerSummary {
+                id: index.to_string(),
+                name: name.to_string(),
+                score: self.scores[index],
+                wins: self.wins[index],
+                has_forfeited: false,
+                is_active: index == self.turn,
+            })
+            .collect()
+    }
+}
@@ -7,13 +7,13 @@ pub struct MineImage {
 }
 
 impl MineImage {
-    pub fn ui(&mut self, ui: &mut egui::Ui) -> Response {
+    pub fn ui(&mut self, ui: &mut egui::Ui, size: f32) -> Response {
         let texture: &egui::TextureHandle = self.texture.get_or_insert_with(|| {
             // Load the texture only once.
             ui.ctx().load_texture("mine", MineImage::load(), Default::default())
         });
 
-        Image::new(texture.id(), [50., 50.]).bg_fill(Color32::from_rgba_premultiplied(150, 29, 27, 100)).ui(ui)
+        Image::new(texture.id(), [size, size]).bg_fill(Color32::from_rgba_premultiplied(150, 29, 27, 100)).ui(ui)
     }
 
     fn load() -> egui::ColorImage {
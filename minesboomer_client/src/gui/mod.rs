@@ -1,2 +1,4 @@
 pub mod gameplay;
+pub mod local;
 pub mod mine_image;
+pub mod replay;
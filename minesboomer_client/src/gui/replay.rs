@@ -0,0 +1,78 @@
+use minesboomer_protocol::{MoveRecord, Replay};
+use minesweeper_multiplayer::Multiplayer;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Folder replays are saved to, next to the settings.
+const REPLAYS_DIR: &str = "replays";
+
+/// Steps through a recorded game one move at a time.
+pub struct ReplayViewer {
+    replay: Replay,
+    /// Number of moves applied to the board shown.
+    position: usize,
+}
+
+impl ReplayViewer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayViewer { replay, position: 0 }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+        let replay = Replay::new_from_json(&contents).map_err(|err| format!("{} is not a replay: {}", path.display(), err))?;
+        Ok(ReplayViewer::new(replay))
+    }
+
+    /// Writes `replay` to a new file in the replays folder and returns its path.
+    pub fn save(replay: &Replay) -> Result<PathBuf, String> {
+        fs::create_dir_all(REPLAYS_DIR).map_err(|err| format!("Unable to create {}: {}", REPLAYS_DIR, err))?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = PathBuf::from(REPLAYS_DIR).join(format!("replay-{}.json", timestamp));
+        fs::write(&path, replay.to_json_string()).map_err(|err| format!("Unable to write {}: {}", path.display(), err))?;
+        Ok(path)
+    }
+
+    /// The game as it was after the moves played so far.
+    /// Moves can't be undone, so the board is rebuilt from the dealt one every time.
+    pub fn game(&self) -> Multiplayer {
+        let mut game = self.replay.difficulty.new_game(["", ""]);
+        game.game.board = self.replay.board.clone().into();
+        for record in &self.replay.moves[..self.position] {
+            game.player_selected(record.coordinates.into());
+        }
+        game
+    }
+
+    pub fn step_forward(&mut self) {
+        self.position = (self.position + 1).min(self.replay.moves.len());
+    }
+
+    pub fn step_back(&mut self) {
+        self.position = self.position.saturating_sub(1);
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn move_count(&self) -> usize {
+        self.replay.moves.len()
+    }
+
+    pub fn players(&self) -> &[String] {
+        &self.replay.players
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.replay.seed
+    }
+
+    /// The move that led to the board shown, if any.
+    pub fn last_move(&self) -> Option<&MoveRecord> {
+        self.position.checked_sub(1).and_then(|index| self.replay.moves.get(index))
+    }
+}
@@ -1,41 +1,86 @@
 mod gui;
 mod networking;
+mod settings;
 
+use clap::Parser;
 use eframe::{App, Frame};
 use minesweeper_multiplayer::{Difficulty, Multiplayer};
 use networking::*;
+use settings::*;
 use std::sync::{Arc, Mutex};
 use std::thread;
 
-use futures::channel::mpsc::unbounded;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver};
 use gui::gameplay::MinesBoomer;
 use tokio_tungstenite::tungstenite::protocol::Message;
 
 struct AppThreadsafeWrapper {
     boomer: Arc<Mutex<MinesBoomer>>,
+    /// Receivers for the ws-client, until it's started by the first connection.
+    /// Local games never need it.
+    network: Option<(UnboundedReceiver<Message>, UnboundedReceiver<String>)>,
+}
+
+impl AppThreadsafeWrapper {
+    fn start_networking(&mut self) {
+        let Some((game_receiver, connect_receiver)) = self.network.take() else {
+            return;
+        };
+        let boomer = Arc::clone(&self.boomer);
+        thread::spawn(move || {
+            let client = WSClient::new(boomer);
+            client.start_listening(game_receiver, connect_receiver);
+        });
+    }
 }
 
 impl App for AppThreadsafeWrapper {
     fn update(&mut self, ctx: &egui::Context, frame: &mut Frame) {
-        self.boomer.lock().unwrap().update(ctx, frame);
+        let is_connecting = {
+            let mut boomer = self.boomer.lock().unwrap();
+            boomer.update(ctx, frame);
+            boomer.is_connecting()
+        };
+        if is_connecting {
+            self.start_networking();
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.boomer.lock().unwrap().save_settings();
     }
 }
 
 fn main() {
     // Internal game->ws-client communication.
     let (game_sender, game_receiver) = unbounded::<Message>();
+    // Server URLs the connection screen asks the ws-client to connect to.
+    let (connect_sender, connect_receiver) = unbounded::<String>();
+
+    let mut settings = Settings::load();
+    settings.apply_cli(Cli::parse());
 
     let game = Multiplayer::new(["Player 1", "Player 2"], Difficulty::Easy);
-    let boomer = MinesBoomer::new(game_sender, game);
-    let boomer_multithread = Arc::new(Mutex::new(boomer));
-    let boomer_multithread_clone = Arc::clone(&boomer_multithread);
+    let title = settings.title.clone();
+    let native_options = eframe::NativeOptions {
+        initial_window_size: settings.dimensions.map(|(width, height)| egui::vec2(width, height)),
+        ..Default::default()
+    };
+    let visuals = settings.visuals();
 
-    thread::spawn(move || {
-        let client = WSClient::new(boomer_multithread_clone);
-        client.start_listening(game_receiver);
-    });
+    let boomer = MinesBoomer::new(game_sender, connect_sender, game, settings);
+    let boomer_multithread = Arc::new(Mutex::new(boomer));
 
-    let app = AppThreadsafeWrapper { boomer: boomer_multithread };
-    let native_options = eframe::NativeOptions::default();
-    eframe::run_native("MinesBooMer", native_options, Box::new(|_| Box::new(app)));
+    let app = AppThreadsafeWrapper {
+        boomer: boomer_multithread,
+        network: Some((game_receiver, connect_receiver)),
+    };
+    eframe::run_native(
+        &title,
+        native_options,
+        Box::new(|cc| {
+            cc.egui_ctx.set_visuals(visuals);
+            Box::new(app)
+        }),
+    );
 }
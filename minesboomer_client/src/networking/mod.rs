@@ -2,29 +2,69 @@ use crate::gui::gameplay::{MinesBoomer, OpenGame};
 use futures::channel::mpsc::UnboundedReceiver;
 use futures::pin_mut;
 use futures_util::{future, StreamExt};
-use minesweeper_multiplayer::messages::*;
+use minesboomer_protocol::{check_version, ServerMessage, VersionMismatch};
 use std::sync::{Arc, Mutex};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream};
+
+const MAX_RECONNECT_ATTEMPTS: u32 = 6;
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
 
 pub struct WSClient {
     game: Arc<Mutex<MinesBoomer>>,
+    /// The last message received, if it was an error. The server sends one to say why it closes the connection.
+    last_error: Mutex<Option<String>>,
 }
 
 impl WSClient {
     pub fn new(game: Arc<Mutex<MinesBoomer>>) -> Self {
-        WSClient { game }
+        WSClient { game, last_error: Mutex::new(None) }
     }
 
     #[tokio::main]
-    pub async fn start_listening(&self, game_receiver: UnboundedReceiver<Message>) {
-        let connect_addr = "ws://0.0.0.0:8080";
+    pub async fn start_listening(&self, mut game_receiver: UnboundedReceiver<Message>, mut connect_receiver: UnboundedReceiver<String>) {
+        // Every server URL submitted from the connection screen starts a new connection.
+        while let Some(connect_addr) = connect_receiver.next().await {
+            let url = match url::Url::parse(&connect_addr) {
+                Ok(url) => url,
+                Err(err) => {
+                    self.game.lock().unwrap().present_connection_error(format!("Invalid server URL: {}", err));
+                    continue;
+                }
+            };
 
-        let url = url::Url::parse(connect_addr).unwrap();
+            let mut attempt = 0;
+            let last_error = loop {
+                println!("connecting...");
+                let last_error = match connect_async(url.clone()).await {
+                    Ok((ws_stream, _)) => {
+                        println!("WebSocket handshake has been successfully completed");
+                        attempt = 0;
+                        self.forward_messages(ws_stream, &mut game_receiver).await;
+                        println!("Disconnected");
+                        // A connection the server closed on purpose isn't worth retrying.
+                        if let Some(reason) = self.last_error.lock().unwrap().take() {
+                            break Some(reason);
+                        }
+                        None
+                    }
+                    Err(err) => Some(format!("Failed to connect: {}", err)),
+                };
 
-        println!("connecting...");
-        let (ws_stream, _) = connect_async(url).await.expect("Failed to connect");
-        println!("WebSocket handshake has been successfully completed");
+                // Dropped connections are retried with an exponential backoff while the server still holds our session.
+                if attempt >= MAX_RECONNECT_ATTEMPTS || !self.game.lock().unwrap().can_resume() {
+                    break last_error;
+                }
+                attempt += 1;
+                self.game.lock().unwrap().present_reconnecting(attempt, MAX_RECONNECT_ATTEMPTS);
+                tokio::time::sleep(RECONNECT_BASE_DELAY * 2u32.pow(attempt - 1)).await;
+            };
+            self.game.lock().unwrap().connection_closed(last_error);
+        }
+    }
 
+    async fn forward_messages(&self, ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>, game_receiver: &mut UnboundedReceiver<Message>) {
         let (sender, receiver) = ws_stream.split();
 
         // Get message from game and forward it to remote.
@@ -33,7 +73,10 @@ impl WSClient {
         // Receive message from remote and handle it.
         let remote_to_game = {
             receiver.for_each(|message| async {
-                self.receive_message(message.unwrap()).await;
+                match message {
+                    Ok(message) => self.receive_message(message).await,
+                    Err(err) => println!("-> Connection error: {}", err),
+                }
             })
         };
 
@@ -42,55 +85,212 @@ impl WSClient {
     }
 
     async fn receive_message(&self, message: Message) {
+        // Control frames (ping, pong, close) are answered by tungstenite itself.
+        if !message.is_text() {
+            return;
+        }
         let string = message.to_string();
-        if let Ok(msg) = serde_json::from_str::<GameStartMessage>(&string) {
-            println!("-> GameStartMessage. active: {}", msg.is_active);
-            let board = msg.get_board();
-            let mut game = self.game.lock().unwrap();
-            game.set_board(board);
-            game.set_is_active(msg.is_active);
-            game.waiting_for_enemy = false;
-            game.close_open_games_menu();
-            println!("Ok.");
-        } else if let Ok(msg) = serde_json::from_str::<CellSelectedMessage>(&string) {
-            println!("-> CellSelectedMessage: {}", msg.to_json_string());
-            let mut game = self.game.lock().unwrap();
-            game.remote_player_selected(msg.coordinates.into());
-            game.set_is_active(msg.is_active_player);
-            println!("Ok.");
-        } else if let Ok(msg) = OpenGamesMessage::new_from_json(&string) {
-            println!("-> OpenGamesMessage: {}", msg.to_json_string());
-            let mut game = self.game.lock().unwrap();
-            let games = msg
-                .games
-                .iter()
-                .map(|game| OpenGame {
-                    name: game.name.clone(),
-                    difficulty: game.difficulty.clone(),
-                    game_id: game.id.clone(),
-                })
-                .collect();
-            game.present_open_games_menu(games);
-            println!("Ok.");
-        } else if let Ok(simple_msg) = serde_json::from_str::<SimpleMessage>(&string) {
-            println!("-> SimpleMessage: {}", simple_msg.name);
-            if simple_msg.name == "identify" {
-                let game = self.game.lock().unwrap();
-                game.request_open_games();
-                game.request_user_id();
-            } else if simple_msg.name == "waiting_enemy" {
+        match ServerMessage::new_from_json(&string) {
+            Ok(message) => {
+                *self.last_error.lock().unwrap() = match &message {
+                    ServerMessage::Error { reason } => Some(reason.clone()),
+                    _ => None,
+                };
+                self.handle_server_message(message);
+            }
+            Err(err) => println!("-> Unrecognized message: {} ({})", string, err),
+        }
+    }
+
+    fn handle_server_message(&self, message: ServerMessage) {
+        match message {
+            ServerMessage::Identify { protocol_version, .. } => {
+                println!("-> Identify. protocol v{}", protocol_version);
+                let mut game = self.game.lock().unwrap();
+                match check_version(protocol_version) {
+                    Ok(()) => game.request_user_id(),
+                    Err(VersionMismatch::TooOld) => {
+                        game.present_connection_error(format!("The server is too old (protocol v{}). Please use an older client.", protocol_version));
+                        game.disconnect();
+                    }
+                    Err(VersionMismatch::TooNew) => {
+                        game.present_connection_error(format!("The server is too new (protocol v{}). Please update your client.", protocol_version));
+                        game.disconnect();
+                    }
+                }
+            }
+            ServerMessage::Welcome { capabilities, session_token, resumed } => {
+                println!("-> Welcome. capabilities: {:?}, resumed: {}", capabilities, resumed);
+                let mut game = self.game.lock().unwrap();
+                game.set_session_token(session_token);
+                game.set_server_capabilities(capabilities);
+                if !resumed {
+                    game.request_open_games();
+                }
+            }
+            ServerMessage::IdentifyRejected { reason, server_version } => {
+                println!("-> IdentifyRejected: {:?}", reason);
+                let mut game = self.game.lock().unwrap();
+                match reason {
+                    VersionMismatch::TooOld => game.present_connection_error(format!("The server is too new (protocol v{}). Please update your client.", server_version)),
+                    VersionMismatch::TooNew => game.present_connection_error(format!("The server is too old (protocol v{}). Please use an older client.", server_version)),
+                }
+            }
+            ServerMessage::OpenGames { games } => {
+                println!("-> OpenGames: {} games", games.len());
+                let mut game = self.game.lock().unwrap();
+                let games = games
+                    .iter()
+                    .map(|game| OpenGame {
+                        name: game.name.clone(),
+                        difficulty: game.difficulty.to_string(),
+                        players: game.players,
+                        seats: game.seats,
+                        game_id: game.id.clone(),
+                        is_running: game.is_running,
+                    })
+                    .collect();
+                game.present_open_games_menu(games);
+            }
+            ServerMessage::LobbyUpdate { players, seats, difficulty, player_id } => {
+                println!("-> LobbyUpdate: {}/{} players", players.len(), seats);
+                let mut game = self.game.lock().unwrap();
+                game.set_lobby(players, seats, difficulty, player_id);
+                game.in_lobby = true;
+                game.close_open_games_menu();
+            }
+            ServerMessage::GameStart { board, difficulty, is_active, players, seed } => {
+                println!("-> GameStart. {:?}, active: {}, seed: {:?}", difficulty, is_active, seed);
+                let mut game = self.game.lock().unwrap();
+                game.start_game(board.into(), difficulty);
+                game.set_seed(seed);
+                game.set_is_active(is_active);
+                game.set_players(players);
+                game.in_lobby = false;
+                game.close_open_games_menu();
+            }
+            ServerMessage::SpectateStart { board, difficulty, players, marks } => {
+                println!("-> SpectateStart. {:?}", difficulty);
+                let mut game = self.game.lock().unwrap();
+                game.start_game(board.into(), difficulty);
+                game.set_marks(marks);
+                game.set_is_active(false);
+                game.set_players(players);
+                game.in_lobby = false;
+                game.close_open_games_menu();
+            }
+            ServerMessage::CellSelected { coordinates, is_active_player, players } => {
+                println!("-> CellSelected. active: {}", is_active_player);
                 let mut game = self.game.lock().unwrap();
-                game.waiting_for_enemy = true;
+                game.remote_player_selected(coordinates.into());
+                game.set_is_active(is_active_player);
+                game.set_players(players);
+            }
+            ServerMessage::BoardDealt { board, missed_no_guess } => {
+                println!("-> BoardDealt. missed no guess: {}", missed_no_guess);
+                let mut game = self.game.lock().unwrap();
+                game.set_board(board.into());
+                if missed_no_guess {
+                    game.present_notice(Some("No board without guessing was found in time, this one only has a safe start".to_owned()));
+                }
+            }
+            ServerMessage::CellMarked { coordinates, mark, player_id } => {
+                println!("-> CellMarked: {:?}", mark);
+                self.game.lock().unwrap().set_cell_mark(coordinates.into(), mark, player_id);
+            }
+            ServerMessage::GameResumed { board, difficulty, is_active, players, marks } => {
+                println!("-> GameResumed. active: {}", is_active);
+                let mut game = self.game.lock().unwrap();
+                game.start_game(board.into(), difficulty);
+                game.set_marks(marks);
+                game.set_is_active(is_active);
+                game.set_players(players);
+                game.in_lobby = false;
                 game.close_open_games_menu();
-            } else if simple_msg.name == "client_disconnected" {
+            }
+            ServerMessage::TurnTimer { remaining_secs, turn_secs, .. } => {
+                self.game.lock().unwrap().set_turn_timer(remaining_secs, turn_secs);
+            }
+            ServerMessage::TurnTimedOut { name, forfeited, is_active, players } => {
+                println!("-> TurnTimedOut: {}, forfeited: {}", name, forfeited);
+                let notice = if forfeited {
+                    format!("{} ran out of time too often and forfeited", name)
+                } else {
+                    format!("{} ran out of time", name)
+                };
+                let mut game = self.game.lock().unwrap();
+                game.set_is_active(is_active);
+                game.set_players(players);
+                game.present_notice(Some(notice));
+            }
+            ServerMessage::PlayerDisconnected { name, grace_secs } => {
+                println!("-> PlayerDisconnected: {}", name);
+                let notice = format!("{} lost the connection, waiting up to {}s for them to come back", name, grace_secs);
+                self.game.lock().unwrap().present_notice(Some(notice));
+            }
+            ServerMessage::PlayerReconnected { name } => {
+                println!("-> PlayerReconnected: {}", name);
+                self.game.lock().unwrap().present_notice(None);
+            }
+            ServerMessage::PlayerLeft { name, players } => {
+                println!("-> PlayerLeft: {}", name);
+                let mut game = self.game.lock().unwrap();
+                game.set_players(players);
+                game.present_notice(Some(format!("{} left the game", name)));
+            }
+            ServerMessage::MoveRejected { reason, .. } => {
+                println!("-> MoveRejected: {}", reason);
+                self.game.lock().unwrap().present_move_rejection(reason.to_string());
+            }
+            ServerMessage::Chat { from, text } => {
+                println!("-> Chat from {}", from);
+                self.game.lock().unwrap().receive_chat(from, text);
+            }
+            ServerMessage::GameOver { winner, players, board } => {
+                println!("-> GameOver. winner: {:?}", winner);
                 let mut game = self.game.lock().unwrap();
-                game.waiting_for_enemy = true;
-            } else if simple_msg.name == "host_disconnected" {
+                game.set_players(players);
+                game.set_is_active(false);
+                game.present_game_over(winner, board.into());
+            }
+            ServerMessage::Replay { replay } => {
+                println!("-> Replay: {} moves", replay.moves.len());
+                self.game.lock().unwrap().save_replay(replay);
+            }
+            ServerMessage::Stats { players } => {
+                println!("-> Stats: {} players", players.len());
+                self.game.lock().unwrap().set_stats(players);
+            }
+            ServerMessage::RematchUpdate { requested_by } => {
+                println!("-> RematchUpdate: {:?}", requested_by);
+                self.game.lock().unwrap().set_rematch_requests(requested_by);
+            }
+            ServerMessage::Kicked => {
+                println!("-> Kicked");
                 let mut game = self.game.lock().unwrap();
+                game.in_lobby = false;
+                game.present_notice(Some("The host removed you from the game".to_owned()));
                 game.present_open_games_menu(vec![]);
                 game.request_open_games();
             }
-            println!("Ok.");
+            ServerMessage::HostDisconnected => {
+                println!("-> HostDisconnected");
+                let mut game = self.game.lock().unwrap();
+                game.in_lobby = false;
+                game.present_open_games_menu(vec![]);
+                game.request_open_games();
+            }
+            ServerMessage::ClientDisconnected => {
+                println!("-> ClientDisconnected");
+                let mut game = self.game.lock().unwrap();
+                game.in_lobby = true;
+            }
+            ServerMessage::Latency { ms } => self.game.lock().unwrap().set_latency(ms),
+            ServerMessage::Error { reason } => {
+                println!("-> Error: {}", reason);
+                self.game.lock().unwrap().present_notice(Some(reason));
+            }
         }
+        println!("Ok.");
     }
 }
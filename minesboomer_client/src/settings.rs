@@ -0,0 +1,94 @@
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+const SETTINGS_PATH: &str = "config/settings.ron";
+
+#[derive(Parser, Debug)]
+#[command(about = "MinesBooMer multiplayer client")]
+pub struct Cli {
+    /// WebSocket URL of the server, e.g. ws://127.0.0.1:8080.
+    #[arg(short, long)]
+    server_url: Option<String>,
+    #[arg(short, long)]
+    nickname: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Dark,
+    Light,
+}
+
+/// Client preferences, stored in `config/settings.ron`.
+/// Missing fields (or a missing file) fall back to the defaults below.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub title: String,
+    pub dimensions: Option<(f32, f32)>,
+    pub server_url: String,
+    pub nickname: String,
+    pub theme: Theme,
+    /// Side length of a board cell, in points.
+    pub cell_size: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            title: "MinesBooMer".to_owned(),
+            dimensions: Some((800., 600.)),
+            server_url: "ws://127.0.0.1:8080".to_owned(),
+            nickname: "".to_owned(),
+            theme: Theme::Dark,
+            cell_size: 50.,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        let Ok(contents) = fs::read_to_string(SETTINGS_PATH) else {
+            println!("No settings found at {}, using defaults", SETTINGS_PATH);
+            return Settings::default();
+        };
+        match ron::from_str(&contents) {
+            Ok(settings) => settings,
+            Err(err) => {
+                println!("Invalid {}, using defaults: {}", SETTINGS_PATH, err);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let pretty = ron::ser::PrettyConfig::default();
+        match ron::ser::to_string_pretty(self, pretty) {
+            Ok(contents) => {
+                let _ = fs::create_dir_all("config");
+                if let Err(err) = fs::write(SETTINGS_PATH, contents) {
+                    println!("Unable to save settings: {}", err);
+                }
+            }
+            Err(err) => println!("Unable to serialize settings: {}", err),
+        }
+    }
+
+    /// Command line values take precedence over the ones stored in the file.
+    pub fn apply_cli(&mut self, cli: Cli) {
+        if let Some(server_url) = cli.server_url {
+            self.server_url = server_url;
+        }
+        if let Some(nickname) = cli.nickname {
+            self.nickname = nickname;
+        }
+    }
+
+    pub fn visuals(&self) -> egui::Visuals {
+        match self.theme {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        }
+    }
+}
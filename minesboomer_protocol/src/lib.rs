@@ -0,0 +1,580 @@
+//! Messages exchanged between the MinesBooMer server and its clients.
+//!
+//! Every frame is a JSON object with a `type` tag naming the message, e.g.
+//! `{"type":"cell_selected","coordinates":{"x":3,"y":4}}`.
+
+use minesweeper_multiplayer::{Board, Difficulty, Multiplayer, Size};
+use minesweeper_multiplayer::serializables::{SerializableBoard, SerializablePoint};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Version of the message set below. Bump it whenever a message changes shape.
+pub const PROTOCOL_VERSION: u32 = 22;
+
+/// Oldest peer version this build can still talk to.
+/// Only raised when older peers can't be understood anymore, i.e. a field they rely on changed or went away.
+/// New messages and optional fields go behind a capability instead.
+pub const MIN_PROTOCOL_VERSION: u32 = 14;
+
+/// `MarkCell`, `CellMarked` and the marks of `SpectateStart` and `GameResumed`.
+pub const CELL_MARKS: &str = "cell_marks";
+/// `Latency` reports after each ping.
+pub const LATENCY: &str = "latency";
+/// `AddBot`.
+pub const BOTS: &str = "bots";
+/// The `seed` of `CreateGame`, `GameStart` and replays.
+pub const SEEDS: &str = "seeds";
+/// The `generation` of `CreateGame` and `BoardDealt`. Games that aren't classic can't be joined without it.
+pub const BOARD_DEALING: &str = "board_dealing";
+
+/// Optional features this build supports, advertised during the identify handshake.
+/// Only the capabilities both peers list are used on a connection.
+pub const CAPABILITIES: &[&str] = &[CELL_MARKS, LATENCY, BOTS, SEEDS, BOARD_DEALING];
+
+/// Why a peer's protocol version was refused.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionMismatch {
+    TooOld,
+    TooNew,
+}
+
+pub fn check_version(peer_version: u32) -> Result<(), VersionMismatch> {
+    if peer_version < MIN_PROTOCOL_VERSION {
+        Err(VersionMismatch::TooOld)
+    } else if peer_version > PROTOCOL_VERSION {
+        Err(VersionMismatch::TooNew)
+    } else {
+        Ok(())
+    }
+}
+
+/// The capabilities from `peer_capabilities` that this build supports too.
+pub fn shared_capabilities(peer_capabilities: &[String]) -> Vec<String> {
+    peer_capabilities.iter().filter(|capability| CAPABILITIES.contains(&capability.as_str())).cloned().collect()
+}
+
+/// Number of seats a game can be created with.
+pub const SEAT_RANGE: std::ops::RangeInclusive<usize> = 2..=6;
+
+/// Longest chat message, in characters.
+pub const MAX_CHAT_LENGTH: usize = 200;
+
+/// Smallest and largest width or height of a custom board.
+pub const BOARD_SIDE_RANGE: std::ops::RangeInclusive<u32> = 5..=30;
+
+/// Board a game can be played with: one of the presets or a custom size.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GameDifficulty {
+    #[default]
+    Easy,
+    Medium,
+    Hard,
+    Custom { width: u32, height: u32, mines: u32 },
+}
+
+impl GameDifficulty {
+    /// Checks that a custom board fits the allowed sizes and leaves some cells free of mines.
+    pub fn validate(&self) -> Result<(), String> {
+        let GameDifficulty::Custom { width, height, mines } = *self else {
+            return Ok(());
+        };
+        if !BOARD_SIDE_RANGE.contains(&width) || !BOARD_SIDE_RANGE.contains(&height) {
+            return Err(format!("Board sides must be between {} and {}", BOARD_SIDE_RANGE.start(), BOARD_SIDE_RANGE.end()));
+        }
+        if mines == 0 || mines >= width * height / 2 {
+            return Err(format!("A {}x{} board needs between 1 and {} mines", width, height, width * height / 2 - 1));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for GameDifficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameDifficulty::Easy => write!(f, "Easy"),
+            GameDifficulty::Medium => write!(f, "Medium"),
+            GameDifficulty::Hard => write!(f, "Hard"),
+            GameDifficulty::Custom { width, height, mines } => write!(f, "{}x{}, {} mines", width, height, mines),
+        }
+    }
+}
+
+impl GameDifficulty {
+    /// A game on a fresh random board of this difficulty.
+    /// The core crate only knows the presets, so custom boards are dealt here.
+    pub fn new_game(self, player_names: [&str; 2]) -> Multiplayer {
+        let mut game = Multiplayer::new(player_names, self.preset());
+        if let GameDifficulty::Custom { width, height, mines } = self {
+            let size = Size {
+                width: width as usize,
+                height: height as usize,
+            };
+            game.game.board = Board::new(mines as i32, size);
+            game.game.total_mines = mines as i32;
+        }
+        game
+    }
+
+    /// Width and height of the boards of this difficulty, the presets' as the core crate deals them.
+    pub fn size(self) -> Size {
+        let (width, height) = match self {
+            GameDifficulty::Easy => (10, 10),
+            GameDifficulty::Medium => (16, 16),
+            GameDifficulty::Hard => (30, 16),
+            GameDifficulty::Custom { width, height, .. } => (width as usize, height as usize),
+        };
+        Size { width, height }
+    }
+
+    /// Mines hidden on the boards of this difficulty.
+    pub fn mine_count(self) -> usize {
+        match self {
+            GameDifficulty::Easy => 11,
+            GameDifficulty::Medium => 41,
+            GameDifficulty::Hard => 99,
+            GameDifficulty::Custom { mines, .. } => mines as usize,
+        }
+    }
+
+    /// The core preset closest to this difficulty. Custom boards get the easy one, whose board is replaced.
+    fn preset(self) -> Difficulty {
+        match self {
+            GameDifficulty::Easy | GameDifficulty::Custom { .. } => Difficulty::Easy,
+            GameDifficulty::Medium => Difficulty::Medium,
+            GameDifficulty::Hard => Difficulty::Hard,
+        }
+    }
+}
+
+/// A game as listed to clients looking for one to join or watch.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GameSummary {
+    pub id: String,
+    pub name: String,
+    pub difficulty: GameDifficulty,
+    pub players: usize,
+    pub seats: usize,
+    /// Running games can only be joined as a spectator.
+    pub is_running: bool,
+}
+
+/// A seated player's standing in a running game.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerSummary {
+    pub id: String,
+    pub name: String,
+    /// Mines found so far.
+    pub score: u32,
+    /// Games won in this series of rematches.
+    pub wins: u32,
+    /// Ran out of time too often: skipped for the rest of the game.
+    pub has_forfeited: bool,
+    pub is_active: bool,
+}
+
+/// A seated player in a game that hasn't started yet.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LobbyPlayer {
+    pub id: String,
+    pub name: String,
+    pub is_ready: bool,
+    pub is_host: bool,
+}
+
+/// A cell selected during a game, in the order the moves were played.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MoveRecord {
+    pub player_id: String,
+    pub player: String,
+    pub coordinates: SerializablePoint,
+    /// Time since the game started.
+    pub elapsed_ms: u64,
+    pub found_mine: bool,
+}
+
+/// Everything needed to play a finished game back: the board as it was dealt and every move.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Replay {
+    pub difficulty: GameDifficulty,
+    pub board: SerializableBoard,
+    pub players: Vec<String>,
+    pub moves: Vec<MoveRecord>,
+    /// Seed the board was dealt from. Missing in replays saved before seeds existed.
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+impl Replay {
+    pub fn new_from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Replays are always serializable")
+    }
+}
+
+/// All-time results of a player, by name.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerStats {
+    pub name: String,
+    pub games: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub mines_found: u32,
+}
+
+/// How well a bot seated by the server plays.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BotStrength {
+    Easy,
+    #[default]
+    Normal,
+    Expert,
+}
+
+impl fmt::Display for BotStrength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotStrength::Easy => write!(f, "Easy"),
+            BotStrength::Normal => write!(f, "Normal"),
+            BotStrength::Expert => write!(f, "Expert"),
+        }
+    }
+}
+
+/// When and how the server deals the mines of a game.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BoardGeneration {
+    /// Dealt when the match starts, the first selection may hit a mine.
+    #[default]
+    Classic,
+    /// Dealt on the first selection, with no mine on that cell or around it.
+    SafeStart,
+    /// Like `SafeStart`, and the rest of the board can be cleared by logic alone.
+    NoGuess,
+}
+
+impl fmt::Display for BoardGeneration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardGeneration::Classic => write!(f, "Classic"),
+            BoardGeneration::SafeStart => write!(f, "Safe start"),
+            BoardGeneration::NoGuess => write!(f, "No guessing"),
+        }
+    }
+}
+
+/// A mark a player puts on a cell they haven't cleared, to remember what they think is under it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CellMark {
+    Flag,
+    Question,
+}
+
+impl CellMark {
+    /// The mark that follows `mark` when a cell is marked again: a flag, a question mark, then nothing.
+    pub fn next(mark: Option<CellMark>) -> Option<CellMark> {
+        match mark {
+            None => Some(CellMark::Flag),
+            Some(CellMark::Flag) => Some(CellMark::Question),
+            Some(CellMark::Question) => None,
+        }
+    }
+}
+
+/// A mark on an uncleared cell and the player who put it there, as found in game snapshots.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MarkedCell {
+    pub coordinates: SerializablePoint,
+    pub mark: CellMark,
+    pub player_id: String,
+}
+
+/// Why the server refused a cell selection.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveRejection {
+    GameNotStarted,
+    GameOver,
+    NotYourTurn,
+    OutOfBounds,
+    AlreadyCleared,
+    Spectator,
+}
+
+impl fmt::Display for MoveRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveRejection::GameNotStarted => write!(f, "The game hasn't started yet"),
+            MoveRejection::GameOver => write!(f, "The game is over"),
+            MoveRejection::NotYourTurn => write!(f, "It's not your turn"),
+            MoveRejection::OutOfBounds => write!(f, "That cell is outside the board"),
+            MoveRejection::AlreadyCleared => write!(f, "That cell is already cleared"),
+            MoveRejection::Spectator => write!(f, "Spectators can't make moves"),
+        }
+    }
+}
+
+/// Messages sent by a client to the server.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Identify {
+        name: String,
+        // Missing in version 1 clients, which are then refused as too old.
+        #[serde(default)]
+        protocol_version: u32,
+        #[serde(default)]
+        capabilities: Vec<String>,
+        /// Token from a previous `Welcome`, to resume that session after a dropped connection.
+        #[serde(default)]
+        session_token: Option<String>,
+    },
+    GamesRequest,
+    /// `name` names the game, players always go by the name they identified with.
+    /// Without a difficulty the server's default one is used. Games have two seats unless asked otherwise.
+    /// A `seed` deals the first match on the board it stands for, rematches get fresh boards.
+    /// Unless `generation` is classic, the board also depends on the first cell selected.
+    CreateGame {
+        name: String,
+        difficulty: Option<GameDifficulty>,
+        seats: Option<usize>,
+        seed: Option<u64>,
+        generation: Option<BoardGeneration>,
+    },
+    /// The game starts once every seated player is ready.
+    SetReady { is_ready: bool },
+    /// Host only, while the game is in the lobby.
+    KickPlayer { player_id: String },
+    /// Host only, while the game is in the lobby: seats a bot, which is always ready.
+    AddBot { strength: BotStrength },
+    /// Host only, while the game is in the lobby.
+    ChangeDifficulty { difficulty: GameDifficulty },
+    JoinGame { game_id: String },
+    SpectateGame { game_id: String },
+    CellSelected { coordinates: SerializablePoint },
+    /// Puts `mark` on an uncleared cell, or takes the cell's mark off without one. Allowed out of turn.
+    MarkCell { coordinates: SerializablePoint, mark: Option<CellMark> },
+    /// Sent to everyone in the sender's game, spectators included.
+    Chat { text: String },
+    /// Once a game is over: asks to play again with the same players. It starts when all of them asked.
+    RequestRematch,
+    /// Leaves the current game and goes back to the list of open games.
+    LeaveGame,
+    StatsRequest,
+    /// Asks for the replay of the sender's game once it's over.
+    ReplayRequest,
+}
+
+/// Messages sent by the server to its clients.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Identify { protocol_version: u32, capabilities: Vec<String> },
+    /// `resumed` is set when `session_token` picked up a held session, a `GameResumed` follows then.
+    Welcome { capabilities: Vec<String>, session_token: String, resumed: bool },
+    IdentifyRejected { reason: VersionMismatch, server_version: u32 },
+    OpenGames { games: Vec<GameSummary> },
+    /// The lobby of a game that hasn't started yet. `player_id` is the recipient's own id.
+    LobbyUpdate {
+        players: Vec<LobbyPlayer>,
+        seats: usize,
+        difficulty: GameDifficulty,
+        player_id: String,
+    },
+    /// The host removed the recipient from their game's lobby.
+    Kicked,
+    /// `seed` deals the same board again, see `CreateGame`.
+    GameStart {
+        board: SerializableBoard,
+        difficulty: GameDifficulty,
+        is_active: bool,
+        players: Vec<PlayerSummary>,
+        /// Missing from servers without seeds.
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    /// Snapshot of a running game sent to a new spectator.
+    SpectateStart {
+        board: SerializableBoard,
+        difficulty: GameDifficulty,
+        players: Vec<PlayerSummary>,
+        /// Empty for clients without cell marks.
+        #[serde(default)]
+        marks: Vec<MarkedCell>,
+    },
+    CellSelected {
+        coordinates: SerializablePoint,
+        is_active_player: bool,
+        players: Vec<PlayerSummary>,
+    },
+    /// The board was dealt again around the first selected cell and replaces the one the game started with.
+    /// Sent right before the `CellSelected` of that cell, in games that don't deal classic boards.
+    BoardDealt {
+        board: SerializableBoard,
+        /// Set when the game asked for no guessing but no such board turned up in time, this one only has a safe start.
+        #[serde(default)]
+        missed_no_guess: bool,
+    },
+    /// `player_id` changed the mark of an uncleared cell.
+    CellMarked {
+        coordinates: SerializablePoint,
+        mark: Option<CellMark>,
+        player_id: String,
+    },
+    /// Full state of the game a reconnecting player was seated in.
+    GameResumed {
+        board: SerializableBoard,
+        difficulty: GameDifficulty,
+        is_active: bool,
+        players: Vec<PlayerSummary>,
+        /// Empty for clients without cell marks.
+        #[serde(default)]
+        marks: Vec<MarkedCell>,
+    },
+    /// Sent every second while a turn is clocked.
+    TurnTimer { player_id: String, remaining_secs: u64, turn_secs: u64 },
+    /// The active player ran out of time and lost their turn, or the whole game once `forfeited`.
+    TurnTimedOut {
+        name: String,
+        forfeited: bool,
+        is_active: bool,
+        players: Vec<PlayerSummary>,
+    },
+    /// A seated player dropped; the game is held for them for `grace_secs`.
+    PlayerDisconnected { name: String, grace_secs: u64 },
+    PlayerReconnected { name: String },
+    /// A seated player left a running game that goes on without them.
+    PlayerLeft { name: String, players: Vec<PlayerSummary> },
+    MoveRejected { coordinates: SerializablePoint, reason: MoveRejection },
+    Chat { from: String, text: String },
+    /// The game ended. `winner` is missing on a draw, `board` is the final board with every cell.
+    GameOver {
+        winner: Option<String>,
+        players: Vec<PlayerSummary>,
+        board: SerializableBoard,
+    },
+    Replay { replay: Replay },
+    /// The players with the most wins.
+    Stats { players: Vec<PlayerStats> },
+    /// Names of the players who asked for a rematch so far.
+    RematchUpdate { requested_by: Vec<String> },
+    /// The game closed, nobody but bots was left playing it.
+    HostDisconnected,
+    ClientDisconnected,
+    /// Round trip time of the last ping the server sent to the recipient.
+    Latency { ms: u64 },
+    Error { reason: String },
+}
+
+impl ClientMessage {
+    pub fn new_from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).expect("Client messages are always serializable")
+    }
+}
+
+impl ServerMessage {
+    pub fn new_from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).expect("Server messages are always serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn frame(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn client_messages_carry_their_type() {
+        let message = ClientMessage::CellSelected { coordinates: SerializablePoint { x: 3, y: 4 } };
+        let json = message.to_json_string();
+        assert_eq!(frame(&json), json!({ "type": "cell_selected", "coordinates": { "x": 3, "y": 4 } }));
+        let Ok(ClientMessage::CellSelected { coordinates }) = ClientMessage::new_from_json(&json) else {
+            panic!("{} didn't parse back", json);
+        };
+        assert_eq!((coordinates.x, coordinates.y), (3, 4));
+    }
+
+    #[test]
+    fn server_messages_carry_their_type() {
+        let message = ServerMessage::Chat { from: "Ada".to_owned(), text: "gg".to_owned() };
+        let json = message.to_json_string();
+        assert_eq!(frame(&json), json!({ "type": "chat", "from": "Ada", "text": "gg" }));
+        let Ok(ServerMessage::Chat { from, text }) = ServerMessage::new_from_json(&json) else {
+            panic!("{} didn't parse back", json);
+        };
+        assert_eq!((from.as_str(), text.as_str()), ("Ada", "gg"));
+    }
+
+    #[test]
+    fn messages_without_fields_are_just_their_type() {
+        assert_eq!(frame(&ClientMessage::GamesRequest.to_json_string()), json!({ "type": "games_request" }));
+        assert_eq!(frame(&ServerMessage::Kicked.to_json_string()), json!({ "type": "kicked" }));
+        assert!(matches!(ClientMessage::new_from_json(r#"{"type":"request_rematch"}"#), Ok(ClientMessage::RequestRematch)));
+    }
+
+    #[test]
+    fn unknown_frames_are_rejected() {
+        for json in [r#"{"type":"self_destruct"}"#, r#"{"coordinates":{"x":3,"y":4}}"#, r#"{"type":"cell_selected"}"#, "identify", ""] {
+            assert!(ClientMessage::new_from_json(json).is_err(), "{}", json);
+            assert!(ServerMessage::new_from_json(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn messages_only_parse_on_their_side() {
+        // Both sides send a chat, with different fields.
+        assert!(ServerMessage::new_from_json(&ClientMessage::Chat { text: "hi".to_owned() }.to_json_string()).is_err());
+        assert!(ClientMessage::new_from_json(&ServerMessage::Latency { ms: 12 }.to_json_string()).is_err());
+    }
+
+    #[test]
+    fn accepts_every_version_between_the_oldest_and_this_one() {
+        assert_eq!(check_version(MIN_PROTOCOL_VERSION), Ok(()));
+        assert_eq!(check_version(PROTOCOL_VERSION), Ok(()));
+    }
+
+    #[test]
+    fn refuses_versions_out_of_range() {
+        assert_eq!(check_version(MIN_PROTOCOL_VERSION - 1), Err(VersionMismatch::TooOld));
+        assert_eq!(check_version(0), Err(VersionMismatch::TooOld));
+        assert_eq!(check_version(PROTOCOL_VERSION + 1), Err(VersionMismatch::TooNew));
+    }
+
+    #[test]
+    fn version_one_clients_are_too_old() {
+        let Ok(ClientMessage::Identify { protocol_version, capabilities, .. }) = ClientMessage::new_from_json(r#"{"type":"identify","name":"Ada"}"#) else {
+            panic!("a bare identify didn't parse");
+        };
+        assert!(capabilities.is_empty());
+        assert_eq!(check_version(protocol_version), Err(VersionMismatch::TooOld));
+    }
+
+    #[test]
+    fn shares_only_the_capabilities_both_sides_know() {
+        let peer = vec![BOTS.to_owned(), "time_travel".to_owned(), CELL_MARKS.to_owned()];
+        assert_eq!(shared_capabilities(&peer), vec![BOTS.to_owned(), CELL_MARKS.to_owned()]);
+        assert!(shared_capabilities(&[]).is_empty());
+
+        let everything: Vec<String> = CAPABILITIES.iter().map(|capability| capability.to_string()).collect();
+        assert_eq!(shared_capabilities(&everything), everything);
+    }
+}
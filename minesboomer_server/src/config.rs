@@ -0,0 +1,210 @@
+use clap::Parser;
+use minesboomer_protocol::{BoardGeneration, GameDifficulty, SEAT_RANGE};
+use serde::Deserialize;
+use std::{fs, path::PathBuf};
+
+const DEFAULT_CONFIG_PATH: &str = "config/server.ron";
+
+#[derive(Parser, Debug)]
+#[command(about = "MinesBooMer multiplayer server")]
+pub struct Cli {
+    /// Path to a RON config file. Defaults to `config/server.ron` when present.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// Address to listen on, e.g. 127.0.0.1.
+    #[arg(short, long)]
+    bind_address: Option<String>,
+    #[arg(short, long)]
+    port: Option<u16>,
+    /// Maximum number of games open or running at the same time.
+    #[arg(long)]
+    max_games: Option<usize>,
+    /// Maximum number of simultaneous WebSocket connections.
+    #[arg(long)]
+    max_connections: Option<usize>,
+    /// Seconds a running game waits for a disconnected player to come back.
+    #[arg(long)]
+    reconnect_grace_secs: Option<u64>,
+    /// Seconds a player has for each move. Games have no clock unless this is set.
+    #[arg(long)]
+    turn_secs: Option<u64>,
+    /// Timed out turns after which a player forfeits the game.
+    #[arg(long)]
+    max_timeouts: Option<u32>,
+    /// Seconds between two pings to each client, 0 to disable them.
+    #[arg(long)]
+    heartbeat_secs: Option<u64>,
+    /// Seconds without hearing from a client before its connection is dropped.
+    #[arg(long)]
+    idle_timeout_secs: Option<u64>,
+    /// SQLite file finished games and player statistics are saved to.
+    #[arg(long)]
+    database_path: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub port: u16,
+    pub max_games: usize,
+    pub max_connections: usize,
+    /// How long a running game is held for a player whose connection dropped.
+    pub reconnect_grace_secs: u64,
+    /// Time a player has for each move. The turn passes when it runs out.
+    /// Off by default, 0 keeps games without a clock.
+    pub turn_secs: u64,
+    pub max_timeouts: u32,
+    /// How often clients are pinged, which also measures their latency. 0 disables pings.
+    pub heartbeat_secs: u64,
+    /// Connections silent for that long, pongs included, are considered dead.
+    pub idle_timeout_secs: u64,
+    pub database_path: PathBuf,
+    pub game_defaults: GameDefaults,
+}
+
+/// Settings applied to new games unless the host asks for something else.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GameDefaults {
+    pub difficulty: GameDifficulty,
+    pub seats: usize,
+    pub generation: BoardGeneration,
+}
+
+impl Default for GameDefaults {
+    fn default() -> Self {
+        GameDefaults {
+            difficulty: GameDifficulty::default(),
+            seats: 2,
+            generation: BoardGeneration::default(),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_address: "0.0.0.0".to_owned(),
+            port: 8080,
+            max_games: 100,
+            max_connections: 500,
+            reconnect_grace_secs: 30,
+            turn_secs: 0,
+            max_timeouts: 3,
+            heartbeat_secs: 5,
+            idle_timeout_secs: 20,
+            database_path: PathBuf::from("minesboomer.db"),
+            game_defaults: GameDefaults::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Reads the config file (if any) and applies the command line overrides on top of it.
+    pub fn load(cli: Cli) -> Result<Self, String> {
+        let mut config = match &cli.config {
+            Some(path) => Self::from_file(path)?,
+            None if PathBuf::from(DEFAULT_CONFIG_PATH).exists() => Self::from_file(&PathBuf::from(DEFAULT_CONFIG_PATH))?,
+            None => ServerConfig::default(),
+        };
+        if let Some(bind_address) = cli.bind_address {
+            config.bind_address = bind_address;
+        }
+        if let Some(port) = cli.port {
+            config.port = port;
+        }
+        if let Some(max_games) = cli.max_games {
+            config.max_games = max_games;
+        }
+        if let Some(max_connections) = cli.max_connections {
+            config.max_connections = max_connections;
+        }
+        if let Some(reconnect_grace_secs) = cli.reconnect_grace_secs {
+            config.reconnect_grace_secs = reconnect_grace_secs;
+        }
+        if let Some(turn_secs) = cli.turn_secs {
+            config.turn_secs = turn_secs;
+        }
+        if let Some(max_timeouts) = cli.max_timeouts {
+            config.max_timeouts = max_timeouts;
+        }
+        if let Some(heartbeat_secs) = cli.heartbeat_secs {
+            config.heartbeat_secs = heartbeat_secs;
+        }
+        if let Some(idle_timeout_secs) = cli.idle_timeout_secs {
+            config.idle_timeout_secs = idle_timeout_secs;
+        }
+        if let Some(database_path) = cli.database_path {
+            config.database_path = database_path;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Rejects settings the server can't run with, rather than misbehaving once players show up.
+    fn validate(&self) -> Result<(), String> {
+        if self.max_timeouts == 0 {
+            return Err("max_timeouts must be at least 1, players would forfeit before their first turn".to_owned());
+        }
+        if self.heartbeat_secs > 0 && self.idle_timeout_secs < self.heartbeat_secs {
+            return Err(format!(
+                "idle_timeout_secs ({}) can't be shorter than heartbeat_secs ({}), clients would be dropped between two pings",
+                self.idle_timeout_secs, self.heartbeat_secs
+            ));
+        }
+        if !SEAT_RANGE.contains(&self.game_defaults.seats) {
+            return Err(format!("game_defaults.seats must be between {} and {}", SEAT_RANGE.start(), SEAT_RANGE.end()));
+        }
+        Ok(())
+    }
+
+    fn from_file(path: &PathBuf) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+        ron::from_str(&contents).map_err(|err| format!("Invalid config file {}: {}", path.display(), err))
+    }
+
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.bind_address, self.port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(ServerConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_forfeiting_without_timeouts() {
+        let config = ServerConfig { max_timeouts: 0, ..ServerConfig::default() };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_idle_timeout_shorter_than_heartbeat() {
+        let config = ServerConfig {
+            heartbeat_secs: 10,
+            idle_timeout_secs: 5,
+            ..ServerConfig::default()
+        };
+        assert!(config.validate().is_err());
+
+        let without_pings = ServerConfig { heartbeat_secs: 0, ..config };
+        assert!(without_pings.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_default_seats_out_of_range() {
+        for seats in [0, 1, 7] {
+            let config = ServerConfig {
+                game_defaults: GameDefaults { seats, ..GameDefaults::default() },
+                ..ServerConfig::default()
+            };
+            assert!(config.validate().is_err());
+        }
+    }
+}
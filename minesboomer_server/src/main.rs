@@ -1,28 +1,45 @@
+mod config;
 mod server;
+mod store;
+use config::*;
 use server::*;
+use store::*;
 
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex},
 };
 
+use clap::Parser;
 use tokio::net::TcpListener;
 
 #[tokio::main]
 async fn main() {
-    let addr = "0.0.0.0:8080".to_string();
-
-    let multi_games: MultiGames = Arc::new(Mutex::new(vec![]));
-
-    let state = PeerMap::new(Mutex::new(HashMap::new()));
-    let players = Players::new(Mutex::new(HashMap::new()));
+    let config = match ServerConfig::load(Cli::parse()) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let addr = config.address();
+    let store = match Store::open(&config.database_path) {
+        Ok(store) => store,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let games: GameRegistry = Arc::new(Mutex::new(HashMap::new()));
+    let sessions = Sessions::new(Mutex::new(HashMap::new()));
 
     // Create the event loop and TCP listener we'll accept connections on.
     let try_socket = TcpListener::bind(&addr).await;
     let listener = try_socket.expect("Failed to bind");
     println!("Listening on: {}", addr);
 
-    let server = Arc::new(Server::new(state.clone(), Arc::clone(&multi_games), Arc::clone(&players)));
+    let server = Arc::new(Server::new(games, sessions, store, config));
 
     // Let's spawn the handling of each connection in a separate task.
     while let Ok((stream, addr)) = listener.accept().await {
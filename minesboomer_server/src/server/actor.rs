@@ -0,0 +1,699 @@
+use super::game::*;
+use super::bot::spawn_bot;
+use super::dealer::deal_around;
+use super::{lock, ClientEvent, ClientHandle, ClientId, ServerError};
+use crate::config::ServerConfig;
+use crate::store::Store;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use minesboomer_protocol::{BoardGeneration, BotStrength, CellMark, GameDifficulty, GameSummary, MoveRejection, ServerMessage, BOARD_DEALING, CELL_MARKS};
+use minesweeper_multiplayer::serializables::*;
+use tokio::sync::{mpsc, oneshot, watch};
+
+/// Every game running on the server, keyed by game id.
+pub type GameRegistry = Arc<Mutex<HashMap<String, GameHandle>>>;
+
+pub type Reply = oneshot::Sender<Result<(), ServerError>>;
+
+/// What connections can ask of a game. Commands carrying a `reply` get told whether they were accepted.
+pub enum GameCommand {
+    Join {
+        name: String,
+        client: ClientHandle,
+        reply: Reply,
+    },
+    Spectate {
+        name: String,
+        client: ClientHandle,
+        reply: Reply,
+    },
+    SetReady {
+        client_id: ClientId,
+        is_ready: bool,
+        reply: Reply,
+    },
+    Kick {
+        client_id: ClientId,
+        player_id: String,
+        reply: Reply,
+    },
+    AddBot {
+        client_id: ClientId,
+        strength: BotStrength,
+        reply: Reply,
+    },
+    ChangeDifficulty {
+        client_id: ClientId,
+        difficulty: GameDifficulty,
+        reply: Reply,
+    },
+    CellSelected {
+        client_id: ClientId,
+        coordinates: SerializablePoint,
+        reply: Reply,
+    },
+    MarkCell {
+        client_id: ClientId,
+        coordinates: SerializablePoint,
+        mark: Option<CellMark>,
+        reply: Reply,
+    },
+    Chat {
+        client_id: ClientId,
+        text: String,
+        reply: Reply,
+    },
+    RequestRematch {
+        client_id: ClientId,
+        reply: Reply,
+    },
+    ReplayRequest {
+        client_id: ClientId,
+        reply: Reply,
+    },
+    Leave {
+        client_id: ClientId,
+    },
+    /// The connection with `client_id` dropped. Replies whether the seat is held for a reconnection.
+    Disconnected {
+        client_id: ClientId,
+        reply: oneshot::Sender<bool>,
+    },
+    /// Gives a held seat to a new connection, which gets `welcome` and then the state of the game.
+    Reconnect {
+        old_client_id: ClientId,
+        client: ClientHandle,
+        welcome: ServerMessage,
+        reply: Reply,
+    },
+    /// The grace period of a held seat ran out.
+    ExpireSeat {
+        client_id: ClientId,
+    },
+}
+
+/// The way into a running game task.
+#[derive(Clone)]
+pub struct GameHandle {
+    inbox: mpsc::UnboundedSender<GameCommand>,
+    /// How the game shows in the open games list, `None` while it isn't listed.
+    listing: watch::Receiver<Option<GameSummary>>,
+}
+
+impl GameHandle {
+    pub fn send(&self, command: GameCommand) {
+        // Commands for a game whose task already finished are dropped with its closed inbox.
+        let _ = self.inbox.send(command);
+    }
+
+    /// Sends the command built by `command` and waits for the game's answer.
+    pub async fn request(&self, game_id: &str, command: impl FnOnce(Reply) -> GameCommand) -> Result<(), ServerError> {
+        let (reply, response) = oneshot::channel();
+        self.inbox.send(command(reply)).map_err(|_| ServerError::UnknownGame(game_id.to_owned()))?;
+        response.await.map_err(|_| ServerError::UnknownGame(game_id.to_owned()))?
+    }
+
+    /// Whether the seat of the player with `client_id` is held after their connection dropped.
+    pub async fn hold_seat(&self, client_id: ClientId) -> bool {
+        let (reply, response) = oneshot::channel();
+        self.send(GameCommand::Disconnected { client_id, reply });
+        response.await.unwrap_or(false)
+    }
+
+    pub fn listing(&self) -> Option<GameSummary> {
+        self.listing.borrow().clone()
+    }
+}
+
+/// Runs `game` in its own task and adds it to `registry` until it closes.
+pub fn spawn_game(game: Game, registry: GameRegistry, store: Arc<Mutex<Store>>, config: ServerConfig) -> GameHandle {
+    let (inbox, commands) = mpsc::unbounded_channel();
+    let (listing_sender, listing) = watch::channel(None);
+    let handle = GameHandle { inbox: inbox.clone(), listing };
+    lock(&registry).insert(game.get_id(), handle.clone());
+
+    let actor = GameActor {
+        game,
+        inbox,
+        listing: listing_sender,
+        registry,
+        store,
+        config,
+        is_closed: false,
+    };
+    tokio::spawn(actor.run(commands));
+    handle
+}
+
+/// Owns a `Game` and applies the commands sent to it one at a time.
+struct GameActor {
+    game: Game,
+    /// A sender to the actor's own inbox, for timers that fire later and for bots.
+    inbox: mpsc::UnboundedSender<GameCommand>,
+    listing: watch::Sender<Option<GameSummary>>,
+    registry: GameRegistry,
+    store: Arc<Mutex<Store>>,
+    config: ServerConfig,
+    is_closed: bool,
+}
+
+impl GameActor {
+    /// A way into the game for the clients it seats itself.
+    fn handle(&self) -> GameHandle {
+        GameHandle {
+            inbox: self.inbox.clone(),
+            listing: self.listing.subscribe(),
+        }
+    }
+
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<GameCommand>) {
+        let is_clocked = self.config.turn_secs > 0;
+        let mut clock = tokio::time::interval(Duration::from_secs(1));
+        self.send_lobby_update();
+        self.publish_listing();
+        while !self.is_closed {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(command) => self.handle_command(command).await,
+                    None => break,
+                },
+                _ = clock.tick(), if is_clocked => self.tick_turn_clock(),
+            }
+            self.publish_listing();
+        }
+        println!("-> Closing game {}", self.game.get_id());
+        lock(&self.registry).remove(&self.game.get_id());
+    }
+
+    async fn handle_command(&mut self, command: GameCommand) {
+        match command {
+            GameCommand::Join { name, client, reply } => {
+                let _ = reply.send(self.handle_join(name, client));
+            }
+            GameCommand::Spectate { name, client, reply } => {
+                let _ = reply.send(self.handle_spectate(name, client));
+            }
+            GameCommand::SetReady { client_id, is_ready, reply } => {
+                let _ = reply.send(self.handle_set_ready(client_id, is_ready));
+            }
+            GameCommand::Kick { client_id, player_id, reply } => {
+                let _ = reply.send(self.handle_kick(client_id, &player_id));
+            }
+            GameCommand::AddBot { client_id, strength, reply } => {
+                let _ = reply.send(self.handle_add_bot(client_id, strength));
+            }
+            GameCommand::ChangeDifficulty { client_id, difficulty, reply } => {
+                let _ = reply.send(self.handle_change_difficulty(client_id, difficulty));
+            }
+            GameCommand::CellSelected { client_id, coordinates, reply } => {
+                let _ = reply.send(self.handle_cell_selected(client_id, coordinates).await);
+            }
+            GameCommand::MarkCell { client_id, coordinates, mark, reply } => {
+                let _ = reply.send(self.handle_mark_cell(client_id, coordinates, mark));
+            }
+            GameCommand::Chat { client_id, text, reply } => {
+                let _ = reply.send(self.handle_chat(client_id, text));
+            }
+            GameCommand::RequestRematch { client_id, reply } => {
+                let _ = reply.send(self.handle_request_rematch(client_id));
+            }
+            GameCommand::ReplayRequest { client_id, reply } => {
+                let _ = reply.send(self.send_replay(client_id));
+            }
+            GameCommand::Leave { client_id } => self.leave(client_id),
+            GameCommand::Disconnected { client_id, reply } => {
+                let _ = reply.send(self.hold_seat(client_id));
+            }
+            GameCommand::Reconnect { old_client_id, client, welcome, reply } => {
+                let _ = reply.send(self.reconnect(old_client_id, client, welcome));
+            }
+            GameCommand::ExpireSeat { client_id } => {
+                if self.game.player_at(client_id).is_some() {
+                    println!("{} didn't come back", client_id);
+                    self.leave(client_id);
+                }
+            }
+        }
+    }
+
+    fn handle_join(&mut self, name: String, client: ClientHandle) -> Result<(), ServerError> {
+        println!("-> Client joined game");
+        self.ensure_client_supported(&client)?;
+        if self.game.is_running() {
+            return Err(ServerError::GameAlreadyStarted);
+        }
+        if self.game.is_full() {
+            return Err(ServerError::GameFull);
+        }
+        self.game.add_player(Player::new(name, client));
+        self.send_lobby_update();
+        Ok(())
+    }
+
+    fn handle_spectate(&mut self, name: String, client: ClientHandle) -> Result<(), ServerError> {
+        println!("-> Spectator joined game");
+        self.ensure_client_supported(&client)?;
+        if !self.game.is_running() {
+            return Err(ServerError::GameNotRunning);
+        }
+        if self.game.is_over() {
+            return Err(ServerError::GameFinished);
+        }
+        let board: SerializableBoard = self.game.get_board().clone().into();
+        let difficulty = self.game.get_difficulty();
+        let players = self.game.player_summaries();
+        let marks = if client.supports(CELL_MARKS) { self.game.marks() } else { vec![] };
+        client.send(ServerMessage::SpectateStart { board, difficulty, players, marks })?;
+        self.game.add_spectator(Player::new(name, client));
+        Ok(())
+    }
+
+    /// Games dealt around their first selection need clients that understand `BoardDealt`.
+    fn ensure_client_supported(&self, client: &ClientHandle) -> Result<(), ServerError> {
+        if self.game.get_generation() != BoardGeneration::Classic && !client.supports(BOARD_DEALING) {
+            return Err(ServerError::MissingCapability(BOARD_DEALING));
+        }
+        Ok(())
+    }
+
+    fn handle_set_ready(&mut self, client_id: ClientId, is_ready: bool) -> Result<(), ServerError> {
+        if self.game.is_running() {
+            return Err(ServerError::GameAlreadyStarted);
+        }
+        let player_id = self.game.player_at(client_id).ok_or(ServerError::NotInGame)?.get_id();
+        self.game.set_ready(player_id, is_ready);
+        if self.game.is_everyone_ready() {
+            self.game.start();
+            self.send_new_game_to_players();
+        } else {
+            self.send_lobby_update();
+        }
+        Ok(())
+    }
+
+    fn handle_kick(&mut self, client_id: ClientId, player_id: &str) -> Result<(), ServerError> {
+        self.ensure_host_in_lobby(client_id)?;
+        let kicked_id = self.game.player_with_id(player_id).ok_or_else(|| ServerError::UnknownPlayer(player_id.to_owned()))?.get_client_id();
+        if kicked_id == client_id {
+            return Err(ServerError::CannotKickHost);
+        }
+        if let Some(kicked) = self.game.remove_player(kicked_id) {
+            println!("-> Kicking {} from game {}", kicked.get_name(), self.game.get_id());
+            self.notify(&kicked, ServerMessage::Kicked);
+            kicked.get_client().notify_event(ClientEvent::LeftGame { game_id: self.game.get_id() });
+        }
+        self.send_lobby_update();
+        Ok(())
+    }
+
+    fn handle_add_bot(&mut self, client_id: ClientId, strength: BotStrength) -> Result<(), ServerError> {
+        self.ensure_host_in_lobby(client_id)?;
+        if self.game.is_full() {
+            return Err(ServerError::GameFull);
+        }
+        let (game_id, game) = (self.game.get_id(), self.handle());
+        let bot = Player::new_bot(format!("{} bot", strength), |player_id| spawn_bot(strength, player_id, game_id, game));
+        println!("-> Seating a {} bot in game {}", strength, self.game.get_id());
+        let bot_id = bot.get_id();
+        self.game.add_player(bot);
+        self.game.set_ready(bot_id, true);
+        if self.game.is_everyone_ready() {
+            self.game.start();
+            self.send_new_game_to_players();
+        } else {
+            self.send_lobby_update();
+        }
+        Ok(())
+    }
+
+    fn handle_change_difficulty(&mut self, client_id: ClientId, difficulty: GameDifficulty) -> Result<(), ServerError> {
+        self.ensure_host_in_lobby(client_id)?;
+        self.game.set_difficulty(difficulty);
+        self.send_lobby_update();
+        Ok(())
+    }
+
+    fn ensure_host_in_lobby(&self, client_id: ClientId) -> Result<(), ServerError> {
+        if self.game.get_host().get_client_id() != client_id {
+            return Err(ServerError::NotHost);
+        }
+        if self.game.is_running() {
+            return Err(ServerError::GameAlreadyStarted);
+        }
+        Ok(())
+    }
+
+    async fn handle_cell_selected(&mut self, client_id: ClientId, coordinates: SerializablePoint) -> Result<(), ServerError> {
+        let Some(player) = self.game.player_at(client_id) else {
+            let reason = MoveRejection::Spectator;
+            let spectator = self.game.participant_at(client_id).ok_or(ServerError::NotInGame)?;
+            return spectator.get_client().send(ServerMessage::MoveRejected { coordinates, reason });
+        };
+        let player_id = player.get_id();
+        if let Err(reason) = self.game.validate_selection(&player_id, coordinates.into()) {
+            println!("-> Rejecting move from {}: {}", client_id, reason);
+            return player.get_client().send(ServerMessage::MoveRejected { coordinates, reason });
+        }
+        if let Some((difficulty, seed, generation)) = self.game.pending_deal() {
+            let start = coordinates.into();
+            // Looking for a board without guesses can take a while, it mustn't hold up the workers other games run on.
+            // The game waits for it, commands sent meanwhile are handled once it's dealt.
+            match tokio::task::spawn_blocking(move || deal_around(difficulty, seed, start, generation)).await {
+                Ok(dealt) => {
+                    self.game.set_dealt(Some(dealt.game));
+                    self.send_board_to_participants(dealt.missed_no_guess);
+                }
+                Err(err) => {
+                    println!("Unable to deal game {} around the first selection: {}", self.game.get_id(), err);
+                    self.game.set_dealt(None);
+                }
+            }
+        }
+        self.game.player_selected(&player_id, coordinates.into());
+        self.send_selected_to_players(coordinates);
+        if self.game.is_over() {
+            self.finish_game();
+        }
+        Ok(())
+    }
+
+    fn handle_mark_cell(&mut self, client_id: ClientId, coordinates: SerializablePoint, mark: Option<CellMark>) -> Result<(), ServerError> {
+        let Some(player) = self.game.player_at(client_id) else {
+            let reason = MoveRejection::Spectator;
+            let spectator = self.game.participant_at(client_id).ok_or(ServerError::NotInGame)?;
+            return spectator.get_client().send(ServerMessage::MoveRejected { coordinates, reason });
+        };
+        if let Err(reason) = self.game.validate_mark(coordinates.into()) {
+            return player.get_client().send(ServerMessage::MoveRejected { coordinates, reason });
+        }
+        let player_id = player.get_id();
+        self.game.set_mark(coordinates.into(), mark, player_id.clone());
+        for participant in self.game.get_participants().into_iter().filter(|participant| participant.get_client().supports(CELL_MARKS)) {
+            let player_id = player_id.clone();
+            self.notify(participant, ServerMessage::CellMarked { coordinates, mark, player_id });
+        }
+        Ok(())
+    }
+
+    fn handle_chat(&mut self, client_id: ClientId, text: String) -> Result<(), ServerError> {
+        let from = self.game.participant_at(client_id).ok_or(ServerError::NotInGame)?.get_name().to_owned();
+        for participant in self.game.get_participants() {
+            let chat = ServerMessage::Chat {
+                from: from.clone(),
+                text: text.clone(),
+            };
+            if let Err(err) = participant.get_client().send(chat) {
+                println!("Unable to deliver chat to {}: {}", participant.get_name(), err);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request_rematch(&mut self, client_id: ClientId) -> Result<(), ServerError> {
+        if !self.game.is_over() {
+            return Err(ServerError::GameNotOver);
+        }
+        let player_id = self.game.player_at(client_id).ok_or(ServerError::NotInGame)?.get_id();
+        self.game.request_rematch(player_id);
+        if self.game.is_rematch_agreed() {
+            println!("-> Starting a rematch of game {}", self.game.get_id());
+            self.game.start_rematch();
+            self.send_new_game_to_players();
+        } else {
+            let requested_by = self.game.rematch_requests();
+            for participant in self.game.get_participants() {
+                self.notify(participant, ServerMessage::RematchUpdate { requested_by: requested_by.clone() });
+            }
+        }
+        Ok(())
+    }
+
+    fn send_replay(&self, client_id: ClientId) -> Result<(), ServerError> {
+        let participant = self.game.participant_at(client_id).ok_or(ServerError::NotInGame)?;
+        if !self.game.is_over() {
+            return Err(ServerError::GameNotOver);
+        }
+        participant.get_client().send(ServerMessage::Replay { replay: self.game.replay() })
+    }
+
+    /// Takes the player with `client_id` out of the game. The next seat takes over as host,
+    /// the game only closes once no person is left playing it.
+    fn leave(&mut self, client_id: ClientId) {
+        let was_over = self.game.is_over();
+        if let Some(player) = self.game.remove_player(client_id) {
+            let name = player.get_name().to_owned();
+            if !self.game.has_human_players() {
+                self.close();
+            } else if !self.game.is_running() {
+                self.send_lobby_update();
+            } else if self.game.player_count() < 2 {
+                // The last player seated wins a match everyone else walked out of,
+                // it ends like any other before going back to waiting for players.
+                if !was_over {
+                    self.finish_game();
+                }
+                self.game.stop();
+                for participant in self.game.get_participants() {
+                    self.notify(participant, ServerMessage::ClientDisconnected);
+                }
+                self.send_lobby_update();
+            } else {
+                let players = self.game.player_summaries();
+                for participant in self.game.get_participants() {
+                    let name = name.clone();
+                    let players = players.clone();
+                    self.notify(participant, ServerMessage::PlayerLeft { name, players });
+                }
+                // Leaving can decide the match, if the one who left was the last who could catch up with the leader.
+                if !was_over && self.game.is_over() {
+                    self.finish_game();
+                }
+                // Everyone still seated may have been waiting on the one who left.
+                if self.game.is_rematch_agreed() {
+                    self.game.start_rematch();
+                    self.send_new_game_to_players();
+                }
+            }
+        } else {
+            self.game.remove_spectator(client_id);
+        }
+    }
+
+    /// Sends everyone still around back to the open games list and ends the game.
+    fn close(&mut self) {
+        let game_id = self.game.get_id();
+        for participant in self.game.get_participants() {
+            self.notify(participant, ServerMessage::HostDisconnected);
+            participant.get_client().notify_event(ClientEvent::LeftGame { game_id: game_id.clone() });
+        }
+        self.is_closed = true;
+    }
+
+    /// Keeps the seat of a player who dropped out of a running game, so they can resume it.
+    fn hold_seat(&mut self, client_id: ClientId) -> bool {
+        let grace_secs = self.config.reconnect_grace_secs;
+        let is_playing = self.game.is_running() && !self.game.is_over();
+        let Some(player) = self.game.player_at(client_id).filter(|_| is_playing && grace_secs > 0) else {
+            self.leave(client_id);
+            return false;
+        };
+        let name = player.get_name().to_owned();
+        println!("-> Holding {}'s seat for {}s", name, grace_secs);
+        for participant in self.game.get_participants().into_iter().filter(|participant| participant.get_client_id() != client_id) {
+            let name = name.clone();
+            self.notify(participant, ServerMessage::PlayerDisconnected { name, grace_secs });
+        }
+        let inbox = self.inbox.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(grace_secs)).await;
+            let _ = inbox.send(GameCommand::ExpireSeat { client_id });
+        });
+        true
+    }
+
+    fn reconnect(&mut self, old_client_id: ClientId, client: ClientHandle, welcome: ServerMessage) -> Result<(), ServerError> {
+        // Only a running game has a board to resume, a client back in the lobby starts over.
+        if !self.game.is_running() {
+            return Err(ServerError::NotInGame);
+        }
+        let client_id = client.id;
+        let marks = if client.supports(CELL_MARKS) { self.game.marks() } else { vec![] };
+        let player = self.game.reconnect_player(old_client_id, client).ok_or(ServerError::NotInGame)?;
+        let name = player.get_name().to_owned();
+        let player_id = player.get_id();
+        let resumed = ServerMessage::GameResumed {
+            board: self.game.get_board().clone().into(),
+            difficulty: self.game.get_difficulty(),
+            is_active: self.game.is_player_active(player_id),
+            players: self.game.player_summaries(),
+            marks,
+        };
+        // Sent from here so that nothing else the game sends can get ahead of them.
+        let client = self.game.player_at(client_id).ok_or(ServerError::NotInGame)?.get_client();
+        client.send(welcome)?;
+        client.send(resumed)?;
+        for participant in self.game.get_participants().into_iter().filter(|participant| participant.get_client_id() != client_id) {
+            self.notify(participant, ServerMessage::PlayerReconnected { name: name.clone() });
+        }
+        Ok(())
+    }
+
+    /// Credits the winner, announces the result and saves the match, however it was decided.
+    fn finish_game(&mut self) {
+        println!("-> Game {} is over", self.game.get_id());
+        self.game.credit_winner();
+        self.send_game_over_to_players();
+        // SQLite blocks, the game carries on without waiting for the record to be written.
+        // Losing the record shouldn't keep the players from seeing the result.
+        let store = Arc::clone(&self.store);
+        let record = self.game.record();
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = lock(&store).record_game(&record) {
+                println!("Unable to save game {}: {}", record.game_id, err);
+            }
+        });
+    }
+
+    /// Enforces the turn clock, once a second.
+    fn tick_turn_clock(&mut self) {
+        if !self.game.is_running() || self.game.is_over() {
+            return;
+        }
+        let turn_limit = Duration::from_secs(self.config.turn_secs);
+        let elapsed = self.game.turn_elapsed();
+        if elapsed >= turn_limit {
+            self.time_out_turn();
+            return;
+        }
+        let Some(player_id) = self.game.active_player().map(|player| player.get_id()) else {
+            return;
+        };
+        let remaining_secs = (turn_limit - elapsed).as_secs_f32().ceil() as u64;
+        for participant in self.game.get_participants() {
+            let timer = ServerMessage::TurnTimer {
+                player_id: player_id.clone(),
+                remaining_secs,
+                turn_secs: self.config.turn_secs,
+            };
+            self.notify(participant, timer);
+        }
+    }
+
+    fn time_out_turn(&mut self) {
+        let Some((name, forfeited)) = self.game.time_out(self.config.max_timeouts) else {
+            return;
+        };
+        println!("-> {} ran out of time in game {} (forfeited: {})", name, self.game.get_id(), forfeited);
+        let players = self.game.player_summaries();
+        for participant in self.game.get_participants() {
+            let timed_out = ServerMessage::TurnTimedOut {
+                name: name.clone(),
+                forfeited,
+                is_active: self.game.is_player_active(participant.get_id()),
+                players: players.clone(),
+            };
+            self.notify(participant, timed_out);
+        }
+        if self.game.is_over() {
+            self.finish_game();
+        }
+    }
+
+    /// Updates the game's entry in the open games list.
+    /// Finished games wait on a rematch from their own players, nobody else can join or watch them.
+    fn publish_listing(&self) {
+        let listing = (!self.is_closed && !self.game.is_over()).then(|| GameSummary {
+            id: self.game.get_id(),
+            name: self.game.get_name().to_owned(),
+            difficulty: self.game.get_difficulty(),
+            players: self.game.player_count(),
+            seats: self.game.get_seat_count(),
+            is_running: self.game.is_running(),
+        });
+        self.listing.send_replace(listing);
+    }
+
+    fn send_lobby_update(&self) {
+        let players = self.game.lobby_players();
+        for player in self.game.get_players() {
+            let update = ServerMessage::LobbyUpdate {
+                players: players.clone(),
+                seats: self.game.get_seat_count(),
+                difficulty: self.game.get_difficulty(),
+                player_id: player.get_id(),
+            };
+            self.notify(player, update);
+        }
+    }
+
+    fn send_selected_to_players(&self, coordinates: SerializablePoint) {
+        let players = self.game.player_summaries();
+        for participant in self.game.get_participants() {
+            let is_active = self.game.is_player_active(participant.get_id());
+            let players = players.clone();
+            self.notify(
+                participant,
+                ServerMessage::CellSelected {
+                    coordinates,
+                    is_active_player: is_active,
+                    players,
+                },
+            );
+        }
+    }
+
+    fn send_game_over_to_players(&self) {
+        let winner = self.game.winner().map(|winner| winner.get_name().to_owned());
+        let players = self.game.player_summaries();
+        let board: SerializableBoard = self.game.get_board().clone().into();
+        for participant in self.game.get_participants() {
+            let game_over = ServerMessage::GameOver {
+                winner: winner.clone(),
+                players: players.clone(),
+                board: board.clone(),
+            };
+            self.notify(participant, game_over);
+        }
+    }
+
+    fn send_board_to_participants(&self, missed_no_guess: bool) {
+        let board: SerializableBoard = self.game.get_board().clone().into();
+        for participant in self.game.get_participants() {
+            self.notify(participant, ServerMessage::BoardDealt { board: board.clone(), missed_no_guess });
+        }
+    }
+
+    fn send_new_game_to_players(&self) {
+        let players = self.game.player_summaries();
+        for participant in self.game.get_participants() {
+            let is_active = self.game.is_player_active(participant.get_id());
+            let board: SerializableBoard = self.game.get_board().clone().into();
+            let difficulty = self.game.get_difficulty();
+            let players = players.clone();
+            let seed = self.game.get_seed();
+            self.notify(
+                participant,
+                ServerMessage::GameStart {
+                    board,
+                    difficulty,
+                    is_active,
+                    players,
+                    seed: Some(seed),
+                },
+            );
+        }
+    }
+
+    /// Sends `message` to `player`, logging instead of failing when they are gone,
+    /// so one dropped player doesn't stop the others from being updated.
+    fn notify(&self, player: &Player, message: ServerMessage) {
+        if let Err(err) = player.get_client().send(message) {
+            println!("Unable to notify {}: {}", player.get_name(), err);
+        }
+    }
+}
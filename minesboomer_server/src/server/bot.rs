@@ -0,0 +1,268 @@
+use super::actor::{GameCommand, GameHandle};
+use super::solver::Solver;
+use super::{ClientHandle, ClientId, ServerError};
+
+use std::time::Duration;
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures_util::StreamExt;
+use minesboomer_protocol::{BotStrength, ClientMessage, GameDifficulty, MoveRejection, PlayerSummary, ServerMessage, CAPABILITIES};
+use minesweeper_multiplayer::{serializables::*, Multiplayer, Point};
+use rand::{seq::SliceRandom, Rng};
+use tokio_tungstenite::tungstenite::Message;
+
+/// How long a bot waits before playing, so people can follow its moves.
+const THINKING_TIME: Duration = Duration::from_millis(800);
+
+/// Seats a bot playing as `player_id` in `game`. It speaks the same protocol as a client connection:
+/// it reads the messages sent to the returned handle and answers with `ClientMessage`s.
+/// The bot stops once it's kicked or its game closes.
+pub fn spawn_bot(strength: BotStrength, player_id: String, game_id: String, game: GameHandle) -> ClientHandle {
+    let (tx, frames) = unbounded();
+    // Bots don't track which game they are in, it's always the one that seated them.
+    let (events, _) = unbounded();
+    // Bots understand everything the server sends.
+    let capabilities = CAPABILITIES.iter().map(|capability| capability.to_string()).collect();
+    let client = ClientHandle {
+        id: ClientId::next(),
+        tx,
+        events,
+        capabilities,
+    };
+    let (outgoing, messages) = unbounded();
+    let bot = Bot {
+        player_id,
+        strength,
+        outgoing,
+        game: None,
+        is_active: false,
+        rejected: vec![],
+    };
+    tokio::spawn(bot.run(frames));
+    tokio::spawn(relay(messages, client.clone(), game_id, game));
+    client
+}
+
+/// Hands the bot's messages to its game, the way a server connection does with a client's,
+/// and reports what the game refused back to the bot.
+async fn relay(mut messages: UnboundedReceiver<Message>, client: ClientHandle, game_id: String, game: GameHandle) {
+    let client_id = client.id;
+    while let Some(frame) = messages.next().await {
+        let Message::Text(text) = frame else {
+            continue;
+        };
+        let result = match ClientMessage::new_from_json(&text) {
+            Ok(ClientMessage::SetReady { is_ready }) => game.request(&game_id, |reply| GameCommand::SetReady { client_id, is_ready, reply }).await,
+            Ok(ClientMessage::CellSelected { coordinates }) => game.request(&game_id, |reply| GameCommand::CellSelected { client_id, coordinates, reply }).await,
+            Ok(ClientMessage::RequestRematch) => game.request(&game_id, |reply| GameCommand::RequestRematch { client_id, reply }).await,
+            Ok(_) => Err(ServerError::NotForBots),
+            Err(err) => Err(ServerError::Unrecognized(err.to_string())),
+        };
+        match result {
+            Ok(()) => {}
+            // The game closed, the bot goes with it.
+            Err(ServerError::UnknownGame(_)) => break,
+            Err(err) => {
+                let _ = client.send(ServerMessage::Error { reason: err.to_string() });
+            }
+        }
+    }
+}
+
+/// A player the server controls.
+/// In MinesBooMer finding a mine scores, so it goes for the cells most likely to hide one
+/// and stays away from the ones it can prove are safe.
+struct Bot {
+    player_id: String,
+    strength: BotStrength,
+    /// Frames for the bot's game, as a client would send them over its connection.
+    outgoing: UnboundedSender<Message>,
+    game: Option<Multiplayer>,
+    is_active: bool,
+    /// Cells the game refused this turn, which the bot doesn't try again.
+    rejected: Vec<Point>,
+}
+
+impl Bot {
+    async fn run(mut self, mut frames: UnboundedReceiver<Message>) {
+        while let Some(frame) = frames.next().await {
+            let Message::Text(text) = frame else {
+                continue;
+            };
+            let Ok(message) = ServerMessage::new_from_json(&text) else {
+                continue;
+            };
+            if !self.handle_server_message(message) {
+                break;
+            }
+            if self.is_active && self.game.is_some() {
+                tokio::time::sleep(THINKING_TIME).await;
+                self.play();
+            }
+        }
+    }
+
+    /// Keeps track of the game, returning false once the bot has nothing left to do in it.
+    fn handle_server_message(&mut self, message: ServerMessage) -> bool {
+        match message {
+            ServerMessage::LobbyUpdate { players, player_id, .. } => {
+                // Games going back to the lobby clear everyone's ready state.
+                if players.iter().any(|player| player.id == player_id && !player.is_ready) {
+                    self.send(ClientMessage::SetReady { is_ready: true });
+                }
+                self.game = None;
+                self.is_active = false;
+            }
+            ServerMessage::GameStart { board, difficulty, players, .. } | ServerMessage::GameResumed { board, difficulty, players, .. } => {
+                self.game = Some(new_game(board, difficulty));
+                self.rejected.clear();
+                self.is_active = self.is_active_in(&players);
+            }
+            ServerMessage::BoardDealt { board, .. } => {
+                if let Some(game) = &mut self.game {
+                    game.game.board = board.into();
+                }
+            }
+            ServerMessage::CellSelected { coordinates, players, .. } => {
+                if let Some(game) = &mut self.game {
+                    game.player_selected(coordinates.into());
+                }
+                self.rejected.clear();
+                self.is_active = self.is_active_in(&players);
+            }
+            // The bot's board was off from the game's, the turn is still its own to play another cell.
+            ServerMessage::MoveRejected {
+                coordinates,
+                reason: MoveRejection::AlreadyCleared | MoveRejection::OutOfBounds,
+            } => {
+                self.rejected.push(coordinates.into());
+                self.is_active = true;
+            }
+            // Whoever leaves or runs out of time may pass the turn to the bot.
+            ServerMessage::TurnTimedOut { players, .. } | ServerMessage::PlayerLeft { players, .. } => self.is_active = self.is_active_in(&players),
+            ServerMessage::GameOver { .. } => {
+                self.game = None;
+                self.is_active = false;
+                // Always up for another round.
+                self.send(ClientMessage::RequestRematch);
+            }
+            ServerMessage::Kicked | ServerMessage::HostDisconnected => return false,
+            _ => {}
+        }
+        true
+    }
+
+    fn is_active_in(&self, players: &[PlayerSummary]) -> bool {
+        players.iter().any(|player| player.id == self.player_id && player.is_active)
+    }
+
+    fn play(&mut self) {
+        let Some(game) = &self.game else {
+            return;
+        };
+        let Some(coordinates) = choose_cell(game, self.strength, &self.rejected) else {
+            return;
+        };
+        // The turn comes back with the server's answer.
+        self.is_active = false;
+        let coordinates: SerializablePoint = coordinates.into();
+        self.send(ClientMessage::CellSelected { coordinates });
+    }
+
+    fn send(&self, message: ClientMessage) {
+        // The relay only stops once the game is gone, the message has no use then.
+        let _ = self.outgoing.unbounded_send(Message::Text(message.to_json_string()));
+    }
+}
+
+fn new_game(board: SerializableBoard, difficulty: GameDifficulty) -> Multiplayer {
+    let mut game = difficulty.new_game(["", ""]);
+    game.game.board = board.into();
+    game
+}
+
+/// The cell the bot selects next, leaving out the `rejected` ones.
+/// Weaker bots sometimes pick one at random instead of thinking it through.
+fn choose_cell(game: &Multiplayer, strength: BotStrength, rejected: &[Point]) -> Option<Point> {
+    let mut chances = Solver::new(game).mine_chances();
+    chances.retain(|(point, _)| !rejected.contains(point));
+    let mut rng = rand::thread_rng();
+    let blunder_chance = match strength {
+        BotStrength::Easy => 0.6,
+        BotStrength::Normal => 0.25,
+        BotStrength::Expert => 0.,
+    };
+    if rng.gen_bool(blunder_chance) {
+        return chances.choose(&mut rng).map(|(point, _)| *point);
+    }
+    let best = chances.iter().map(|(_, chance)| *chance).fold(0., f32::max);
+    let candidates: Vec<Point> = chances.iter().filter(|(_, chance)| *chance >= best).map(|(point, _)| *point).collect();
+    candidates.choose(&mut rng).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bot() -> Bot {
+        let (outgoing, _) = unbounded();
+        Bot {
+            player_id: "bot".to_owned(),
+            strength: BotStrength::Expert,
+            outgoing,
+            game: None,
+            is_active: false,
+            rejected: vec![],
+        }
+    }
+
+    fn summary(id: &str, is_active: bool) -> PlayerSummary {
+        PlayerSummary {
+            id: id.to_owned(),
+            name: id.to_owned(),
+            score: 0,
+            wins: 0,
+            has_forfeited: false,
+            is_active,
+        }
+    }
+
+    #[test]
+    fn takes_the_turn_passed_on_by_a_leaving_player() {
+        let mut bot = bot();
+        let players = vec![summary("host", false), summary("bot", true)];
+        bot.handle_server_message(ServerMessage::PlayerLeft { name: "guest".to_owned(), players });
+        assert!(bot.is_active);
+    }
+
+    #[test]
+    fn waits_while_someone_else_is_active() {
+        let mut bot = bot();
+        bot.is_active = true;
+        let players = vec![summary("host", true), summary("bot", false)];
+        bot.handle_server_message(ServerMessage::TurnTimedOut { name: "bot".to_owned(), forfeited: false, is_active: false, players });
+        assert!(!bot.is_active);
+    }
+
+    #[test]
+    fn plays_another_cell_after_a_rejected_one() {
+        let mut bot = bot();
+        let coordinates: SerializablePoint = Point { x: 2, y: 3 }.into();
+        bot.handle_server_message(ServerMessage::MoveRejected { coordinates, reason: MoveRejection::AlreadyCleared });
+        assert!(bot.is_active);
+        assert_eq!(bot.rejected, vec![Point { x: 2, y: 3 }]);
+
+        let game = GameDifficulty::Easy.new_game(["", ""]);
+        let every_cell = Solver::new(&game).mine_chances().into_iter().map(|(point, _)| point).collect::<Vec<_>>();
+        assert_eq!(choose_cell(&game, BotStrength::Expert, &every_cell), None);
+    }
+
+    #[test]
+    fn does_not_retry_out_of_turn() {
+        let mut bot = bot();
+        let coordinates: SerializablePoint = Point { x: 2, y: 3 }.into();
+        bot.handle_server_message(ServerMessage::MoveRejected { coordinates, reason: MoveRejection::NotYourTurn });
+        assert!(!bot.is_active);
+        assert!(bot.rejected.is_empty());
+    }
+}
@@ -0,0 +1,29 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Number of chat messages a client may send within `CHAT_WINDOW`.
+const CHAT_BURST: usize = 5;
+const CHAT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Tracks when a client last chatted, to keep it from flooding its game.
+#[derive(Default)]
+pub struct ChatLimiter {
+    sent: VecDeque<Instant>,
+}
+
+impl ChatLimiter {
+    /// Records a message, or returns false if the client already sent too many recently.
+    pub fn try_send(&mut self) -> bool {
+        let now = Instant::now();
+        while self.sent.front().is_some_and(|sent_at| now.duration_since(*sent_at) >= CHAT_WINDOW) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= CHAT_BURST {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
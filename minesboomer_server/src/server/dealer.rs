@@ -0,0 +1,208 @@
+use super::solver::Solver;
+
+use minesboomer_protocol::{BoardGeneration, GameDifficulty};
+use minesweeper_multiplayer::{Board, Cell, Multiplayer, Point, Size};
+use rand::{seq::index::sample, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Boards the no-guess generator tries before settling for one that only has a safe start.
+const NO_GUESS_ATTEMPTS: usize = 500;
+
+/// A board dealt around the first selection of a match.
+pub struct Dealt {
+    pub game: Multiplayer,
+    /// Set when no board without guesses turned up in time and this one only has a safe start.
+    pub missed_no_guess: bool,
+}
+
+/// Deals the board of `difficulty` that `seed` stands for.
+/// The same seed always deals the same board, on any server, so tables can play the same game.
+pub fn deal(difficulty: GameDifficulty, seed: u64) -> Multiplayer {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let board = place_mines(difficulty.size(), difficulty.mine_count(), &mut rng, None);
+    new_game(difficulty, board)
+}
+
+/// Deals the board of `difficulty` for a game whose first selection is `start`, keeping it and its neighbours free of mines.
+/// `BoardGeneration::NoGuess` only settles for a board that can be cleared from `start` by logic alone.
+/// Like `deal`, the same seed and start always deal the same board.
+/// Searching for a board without guesses can take a while, so this belongs on a blocking thread.
+pub fn deal_around(difficulty: GameDifficulty, seed: u64, start: Point, generation: BoardGeneration) -> Dealt {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let size = difficulty.size();
+    let mine_count = difficulty.mine_count();
+    if generation != BoardGeneration::NoGuess {
+        let board = place_mines(size, mine_count, &mut rng, Some(start));
+        return Dealt { game: new_game(difficulty, board), missed_no_guess: false };
+    }
+    for _ in 0..NO_GUESS_ATTEMPTS {
+        let board = place_mines(size, mine_count, &mut rng, Some(start));
+        if is_solvable(difficulty, &board, start) {
+            return Dealt { game: new_game(difficulty, board), missed_no_guess: false };
+        }
+    }
+    println!("No board without guesses found for seed {}, dealing one with a safe start only", seed);
+    let board = place_mines(size, mine_count, &mut rng, Some(start));
+    Dealt { game: new_game(difficulty, board), missed_no_guess: true }
+}
+
+fn new_game(difficulty: GameDifficulty, board: Board) -> Multiplayer {
+    let mut game = difficulty.new_game(["", ""]);
+    game.game.board = board;
+    game
+}
+
+fn place_mines(size: Size, mine_count: usize, rng: &mut ChaCha8Rng, start: Option<Point>) -> Board {
+    let height = size.height;
+    let mut spots: Vec<Point> = (0..size.width * height)
+        .map(|index| Point {
+            x: index / height,
+            y: index % height,
+        })
+        .collect();
+    if let Some(start) = start {
+        // Crowded custom boards may not have room to keep the whole area around the start clear.
+        let away: Vec<Point> = spots.iter().copied().filter(|spot| !is_next_to(*spot, start)).collect();
+        if away.len() >= mine_count {
+            spots = away;
+        }
+    }
+    let mines: Vec<Point> = sample(rng, spots.len(), mine_count).into_iter().map(|index| spots[index]).collect();
+    board_with_mines(size, &mines)
+}
+
+fn board_with_mines(size: Size, mines: &[Point]) -> Board {
+    let mut board = Board::new_empty(size);
+    for mine in mines {
+        board.replace_cell(Cell::new_mine(*mine), *mine);
+    }
+    board.add_cell_numbers();
+    board
+}
+
+/// Whether `spot` is `start` or one of its neighbours.
+fn is_next_to(spot: Point, start: Point) -> bool {
+    (spot.x as i64 - start.x as i64).abs() <= 1 && (spot.y as i64 - start.y as i64).abs() <= 1
+}
+
+/// Plays `board` from `start`, only ever selecting cells the solver proves to be mines or safe.
+fn is_solvable(difficulty: GameDifficulty, board: &Board, start: Point) -> bool {
+    let mut game = difficulty.new_game(["", ""]);
+    game.game.board = board.clone();
+    game.player_selected(start);
+    loop {
+        let solver = Solver::new(&game);
+        if solver.is_solved() {
+            return true;
+        }
+        let mut has_progressed = false;
+        for (point, _) in solver.proven() {
+            // Clearing one safe cell can open up others proven in the same pass.
+            if game.get_board().cell_at(point).is_some_and(|cell| !cell.cleared) {
+                game.player_selected(point);
+                has_progressed = true;
+            }
+        }
+        if !has_progressed {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mines_of(game: &Multiplayer) -> Vec<Point> {
+        let mut mines = vec![];
+        game.get_board().for_each_cell(|point, cell, _| {
+            if cell.is_mine() {
+                mines.push(point);
+            }
+        });
+        mines
+    }
+
+    #[test]
+    fn same_seed_deals_same_board() {
+        let custom = GameDifficulty::Custom { width: 12, height: 7, mines: 20 };
+        for difficulty in [GameDifficulty::Easy, GameDifficulty::Medium, GameDifficulty::Hard, custom] {
+            for seed in [0, 1, 42, u64::MAX] {
+                assert_eq!(mines_of(&deal(difficulty, seed)), mines_of(&deal(difficulty, seed)));
+            }
+        }
+    }
+
+    #[test]
+    fn different_seeds_deal_different_boards() {
+        assert_ne!(mines_of(&deal(GameDifficulty::Hard, 1)), mines_of(&deal(GameDifficulty::Hard, 2)));
+    }
+
+    #[test]
+    fn safe_start_keeps_mines_away_from_the_start() {
+        let corner = Point { x: 0, y: 0 };
+        let middle = Point { x: 5, y: 5 };
+        for difficulty in [GameDifficulty::Easy, GameDifficulty::Hard] {
+            for seed in 0..20 {
+                for start in [corner, middle] {
+                    let dealt = deal_around(difficulty, seed, start, BoardGeneration::SafeStart);
+                    assert!(mines_of(&dealt.game).iter().all(|mine| !is_next_to(*mine, start)), "{:?} seed {} start {:?}", difficulty, seed, start);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn safe_start_leaves_no_room_on_crowded_boards() {
+        // 9 cells, 8 mines: only the start itself can be kept clear.
+        let crowded = GameDifficulty::Custom { width: 3, height: 3, mines: 8 };
+        let start = Point { x: 1, y: 1 };
+        let dealt = deal_around(crowded, 3, start, BoardGeneration::SafeStart);
+        assert_eq!(mines_of(&dealt.game).len(), 8);
+    }
+
+    #[test]
+    fn no_guess_boards_on_easy_can_be_solved() {
+        for seed in 0..20 {
+            let start = Point { x: 4, y: 4 };
+            let dealt = deal_around(GameDifficulty::Easy, seed, start, BoardGeneration::NoGuess);
+            assert!(!dealt.missed_no_guess, "seed {}", seed);
+            assert!(is_solvable(GameDifficulty::Easy, dealt.game.get_board(), start), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn no_guess_boards_on_hard_are_found() {
+        for seed in 0..3 {
+            let dealt = deal_around(GameDifficulty::Hard, seed, Point { x: 15, y: 8 }, BoardGeneration::NoGuess);
+            assert!(!dealt.missed_no_guess, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn same_seed_and_start_deal_same_no_guess_board() {
+        let start = Point { x: 2, y: 6 };
+        let first = deal_around(GameDifficulty::Easy, 9, start, BoardGeneration::NoGuess);
+        let second = deal_around(GameDifficulty::Easy, 9, start, BoardGeneration::NoGuess);
+        assert_eq!(mines_of(&first.game), mines_of(&second.game));
+    }
+
+    #[test]
+    fn dealt_boards_match_the_core_presets() {
+        let custom = GameDifficulty::Custom { width: 12, height: 7, mines: 20 };
+        for difficulty in [GameDifficulty::Easy, GameDifficulty::Medium, GameDifficulty::Hard, custom] {
+            let preset = difficulty.new_game(["", ""]);
+            let dealt = deal(difficulty, 5);
+            let (preset_size, dealt_size) = (preset.get_board_dimentions(), dealt.get_board_dimentions());
+            assert_eq!((preset_size.width, preset_size.height), (dealt_size.width, dealt_size.height), "{:?}", difficulty);
+            assert_eq!(mines_of(&dealt).len() as u32, preset.game.remaining_mines(), "{:?}", difficulty);
+        }
+    }
+
+    #[test]
+    fn deals_every_mine_of_the_difficulty() {
+        let game = deal(GameDifficulty::Hard, 7);
+        assert_eq!(mines_of(&game).len(), 99);
+        assert_eq!(game.game.remaining_mines(), 99);
+    }
+}
@@ -0,0 +1,66 @@
+use super::ClientId;
+use minesboomer_protocol::MAX_CHAT_LENGTH;
+use std::fmt;
+
+/// Errors raised while handling a client's message.
+/// They are reported back to that client and never bring the server down.
+#[derive(Debug)]
+pub enum ServerError {
+    NotIdentified,
+    Unrecognized(String),
+    NotForBots,
+    AlreadyIdentified,
+    NotInGame,
+    UnknownGame(String),
+    GameFull,
+    GameNotRunning,
+    GameAlreadyStarted,
+    GameNotOver,
+    GameFinished,
+    NotHost,
+    UnknownPlayer(String),
+    CannotKickHost,
+    InvalidSeats(usize),
+    AlreadyInGame,
+    TooManyGames,
+    InvalidDifficulty(String),
+    EmptyChat,
+    ChatTooLong,
+    ChatRateLimited,
+    Storage(String),
+    MissingCapability(&'static str),
+    ConnectionClosed(ClientId),
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::NotIdentified => write!(f, "Identify before sending other messages"),
+            ServerError::Unrecognized(reason) => write!(f, "Unrecognized message: {}", reason),
+            ServerError::NotForBots => write!(f, "Bots only play the game that seated them"),
+            ServerError::AlreadyIdentified => write!(f, "You have already identified on this connection"),
+            ServerError::NotInGame => write!(f, "You are not playing any game"),
+            ServerError::UnknownGame(id) => write!(f, "The game {} does not exist anymore", id),
+            ServerError::GameFull => write!(f, "The game is already full"),
+            ServerError::GameNotRunning => write!(f, "The game hasn't started yet"),
+            ServerError::GameAlreadyStarted => write!(f, "The game has already started"),
+            ServerError::GameNotOver => write!(f, "The game isn't over yet"),
+            ServerError::GameFinished => write!(f, "The game is already over"),
+            ServerError::NotHost => write!(f, "Only the host can do that"),
+            ServerError::UnknownPlayer(player_id) => write!(f, "There is no player {} in this game", player_id),
+            ServerError::CannotKickHost => write!(f, "The host can't kick themselves"),
+            ServerError::InvalidSeats(seats) => write!(f, "A game can't have {} seats", seats),
+            ServerError::AlreadyInGame => write!(f, "You are already in a game"),
+            ServerError::TooManyGames => write!(f, "Too many games are running, try again later"),
+            ServerError::InvalidDifficulty(reason) => write!(f, "Invalid board: {}", reason),
+            ServerError::EmptyChat => write!(f, "Chat messages can't be empty"),
+            ServerError::ChatTooLong => write!(f, "Chat messages can be at most {} characters long", MAX_CHAT_LENGTH),
+            ServerError::ChatRateLimited => write!(f, "You are sending messages too fast, slow down"),
+            ServerError::Storage(reason) => write!(f, "The server's database failed: {}", reason),
+            ServerError::MissingCapability(capability) => write!(f, "Your client is too old for this game, it lacks {}", capability),
+            ServerError::ConnectionClosed(client_id) => write!(f, "The connection to {} is closed", client_id),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
@@ -1,110 +1,728 @@
-use minesweeper_multiplayer::{Board, Difficulty, Multiplayer, Point};
-use std::net::SocketAddr;
+use super::{ClientHandle, ClientId};
+use super::dealer::deal;
+use crate::store::GameRecord;
+use minesboomer_protocol::{BoardGeneration, CellMark, GameDifficulty, LobbyPlayer, MarkedCell, MoveRecord, MoveRejection, PlayerSummary, Replay};
+use minesweeper_multiplayer::{Board, Multiplayer, Point};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
 use uuid::Uuid;
 
 pub struct Player {
     id: String,
     name: String,
-    game_id: String,
-    address: SocketAddr,
+    client: ClientHandle,
+    /// Bots play like anyone else, but stay out of the player statistics.
+    is_bot: bool,
 }
 
 impl Player {
-    pub fn new(name: String, game_id: impl Into<String>, address: SocketAddr) -> Self {
+    pub fn new(name: String, client: ClientHandle) -> Self {
         Player {
             id: Uuid::new_v4().to_string(),
             name,
-            game_id: game_id.into(),
-            address,
+            client,
+            is_bot: false,
         }
     }
 
-    pub fn game_id(&self) -> String {
-        self.game_id.clone()
+    /// A player the server controls. Its client is spawned knowing the id it plays under.
+    pub fn new_bot(name: String, spawn: impl FnOnce(String) -> ClientHandle) -> Self {
+        let id = Uuid::new_v4().to_string();
+        Player {
+            client: spawn(id.clone()),
+            id,
+            name,
+            is_bot: true,
+        }
     }
 
     pub fn get_id(&self) -> String {
         self.id.clone()
     }
 
-    pub fn get_address(&self) -> SocketAddr {
-        self.address
+    pub fn get_client_id(&self) -> ClientId {
+        self.client.id
     }
 
     pub fn get_name(&self) -> &str {
         &self.name
     }
+
+    pub fn get_client(&self) -> &ClientHandle {
+        &self.client
+    }
 }
 
 pub struct Game {
     id: String,
-    host: Player,
-    client: Option<Player>,
+    name: String,
+    /// Seated players in turn order. The first one is the host.
+    seats: Vec<Player>,
+    seat_count: usize,
+    spectators: Vec<Player>,
     multi_game: Multiplayer,
+    difficulty: GameDifficulty,
+    /// Seed the host asked for, used by the next match dealt.
+    requested_seed: Option<u64>,
+    /// Seed the current board was dealt from.
+    seed: u64,
+    generation: BoardGeneration,
+    /// False until the first selection of the match when the board is dealt around it.
+    is_dealt: bool,
+    is_running: bool,
+    /// Index in `seats` of the player whose turn it is.
+    turn: usize,
+    /// Mines found by each player, by player id.
+    scores: HashMap<String, u32>,
+    /// Ids of the players ready to start, while the game is in the lobby.
+    ready: HashSet<String>,
+    /// Ids of the players who want a rematch, once the game is over.
+    rematch: HashSet<String>,
+    /// Index in `seats` of the player who starts, alternated on each rematch.
+    starter: usize,
+    /// Games won by each player in this series, by player id.
+    wins: HashMap<String, u32>,
+    /// Moves of the current match, in order.
+    moves: Vec<MoveRecord>,
+    /// Marks on uncleared cells with the id of the player who put each of them there.
+    marks: Vec<(Point, CellMark, String)>,
+    /// The board of the current match as it was dealt, for replays.
+    initial_board: Board,
+    started_at: Instant,
+    turn_started_at: Instant,
+    /// Turns each player let run out in the current match, by player id.
+    timeouts: HashMap<String, u32>,
+    /// Ids of the players who ran out of time too often. They are skipped and can't win anymore.
+    forfeited: HashSet<String>,
 }
 
 impl Game {
-    pub fn new(player: Player, id: impl Into<String>) -> Self {
+    pub fn new(player: Player, id: impl Into<String>, name: String, difficulty: GameDifficulty, seat_count: usize, seed: Option<u64>, generation: BoardGeneration) -> Self {
+        let multi_game = difficulty.new_game(["", ""]);
         Game {
-            host: player,
-            client: None,
-            multi_game: Multiplayer::new(["", ""], Difficulty::Easy),
+            seats: vec![player],
+            seat_count,
+            spectators: vec![],
+            initial_board: multi_game.get_board().clone(),
+            multi_game,
+            difficulty,
+            requested_seed: seed,
+            seed: 0,
+            generation,
+            is_dealt: true,
+            is_running: false,
+            turn: 0,
+            scores: HashMap::new(),
+            ready: HashSet::new(),
+            rematch: HashSet::new(),
+            starter: 0,
+            wins: HashMap::new(),
+            moves: vec![],
+            marks: vec![],
+            started_at: Instant::now(),
+            turn_started_at: Instant::now(),
+            timeouts: HashMap::new(),
+            forfeited: HashSet::new(),
             id: id.into(),
+            name,
+        }
+    }
+
+    /// Deals a fresh board and starts the match with the starter's turn.
+    /// Turns and scores are tracked here, `multi_game` only reveals the board.
+    pub fn start(&mut self) {
+        self.seed = self.requested_seed.take().unwrap_or_else(rand::random);
+        self.multi_game = deal(self.difficulty, self.seed);
+        self.initial_board = self.multi_game.get_board().clone();
+        self.is_dealt = self.generation == BoardGeneration::Classic;
+        self.scores.clear();
+        self.ready.clear();
+        self.rematch.clear();
+        self.moves.clear();
+        self.marks.clear();
+        self.timeouts.clear();
+        self.forfeited.clear();
+        self.started_at = Instant::now();
+        self.turn_started_at = Instant::now();
+        self.starter %= self.seats.len();
+        self.turn = self.starter;
+        self.is_running = true;
+    }
+
+    /// What the board of the match is dealt from, while it waits for the first selection to be dealt around.
+    /// Classic boards are dealt when the match starts.
+    pub fn pending_deal(&self) -> Option<(GameDifficulty, u64, BoardGeneration)> {
+        (!self.is_dealt).then_some((self.difficulty, self.seed, self.generation))
+    }
+
+    /// Ends the wait for the first selection with the board dealt around it,
+    /// or without one, with the board dealt when the match started.
+    pub fn set_dealt(&mut self, dealt: Option<Multiplayer>) {
+        self.is_dealt = true;
+        if let Some(multi_game) = dealt {
+            self.multi_game = multi_game;
+            self.initial_board = self.multi_game.get_board().clone();
         }
     }
 
-    pub fn generate_multi_game(&mut self) {
-        let mut multi_game = Multiplayer::new([&self.host.name, &self.client.as_ref().unwrap().name], Difficulty::Easy);
-        multi_game.players[0].id = self.host.get_id();
-        multi_game.players[1].id = self.client.as_ref().unwrap().get_id();
-        self.multi_game = multi_game;
+    /// Starts another match with the same players, the next seat moving first.
+    pub fn start_rematch(&mut self) {
+        self.starter += 1;
+        self.start();
+    }
+
+    /// Puts the game back in the lobby, where everyone has to get ready again.
+    pub fn stop(&mut self) {
+        self.ready.clear();
+        self.rematch.clear();
+        self.is_running = false;
     }
 
     pub fn get_board(&self) -> &Board {
         self.multi_game.get_board()
     }
 
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_generation(&self) -> BoardGeneration {
+        self.generation
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
     pub fn get_id(&self) -> String {
         self.id.clone()
     }
 
-    pub fn get_client(&self) -> Option<&Player> {
-        self.client.as_ref()
+    pub fn add_player(&mut self, player: Player) {
+        self.seats.push(player);
+    }
+
+    /// Frees the seat of the player with `client_id`, keeping the turn with the same player when possible.
+    pub fn remove_player(&mut self, client_id: ClientId) -> Option<Player> {
+        let index = self.seats.iter().position(|player| player.get_client_id() == client_id)?;
+        let player = self.seats.remove(index);
+        self.ready.remove(&player.id);
+        self.timeouts.remove(&player.id);
+        self.forfeited.remove(&player.id);
+        self.rematch.remove(&player.id);
+        self.wins.remove(&player.id);
+        let was_their_turn = index == self.turn;
+        if index < self.starter {
+            self.starter -= 1;
+        }
+        if index < self.turn {
+            self.turn -= 1;
+        }
+        if self.turn >= self.seats.len() {
+            self.turn = 0;
+        }
+        if was_their_turn {
+            // The next player still in the match gets a full turn.
+            self.skip_forfeited();
+            self.turn_started_at = Instant::now();
+        }
+        Some(player)
+    }
+
+    /// Whether anyone but bots is still seated, bots don't keep a game open on their own.
+    pub fn has_human_players(&self) -> bool {
+        self.seats.iter().any(|player| !player.is_bot)
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.seats.len()
+    }
+
+    pub fn get_seat_count(&self) -> usize {
+        self.seat_count
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.seats.len() >= self.seat_count
     }
 
-    pub fn set_client(&mut self, client: Player) {
-        self.client = Some(client);
+    pub fn is_running(&self) -> bool {
+        self.is_running
     }
 
-    pub fn remove_client(&mut self) {
-        self.client = None;
+    pub fn add_spectator(&mut self, spectator: Player) {
+        self.spectators.push(spectator);
     }
 
-    pub fn has_client(&self) -> bool {
-        self.client.is_some()
+    /// Returns whether the player with `client_id` was watching this game.
+    pub fn remove_spectator(&mut self, client_id: ClientId) -> bool {
+        let count = self.spectators.len();
+        self.spectators.retain(|spectator| spectator.get_client_id() != client_id);
+        self.spectators.len() != count
     }
 
     pub fn get_host(&self) -> &Player {
-        &self.host
+        &self.seats[0]
+    }
+
+    pub fn get_difficulty(&self) -> GameDifficulty {
+        self.difficulty
     }
 
-    pub fn get_difficulty(&self) -> &Difficulty {
-        self.multi_game.get_difficulty()
+    /// Changes the board of the next match. Players agreed to the previous one, so nobody is ready anymore.
+    pub fn set_difficulty(&mut self, difficulty: GameDifficulty) {
+        self.difficulty = difficulty;
+        self.ready.clear();
     }
 
-    pub fn player_selected(&mut self, coordinates: Point) {
+    pub fn set_ready(&mut self, player_id: String, is_ready: bool) {
+        if is_ready {
+            self.ready.insert(player_id);
+        } else {
+            self.ready.remove(&player_id);
+        }
+    }
+
+    /// Whether the lobby can start: at least two players, all of them ready.
+    pub fn is_everyone_ready(&self) -> bool {
+        self.seats.len() >= 2 && self.seats.iter().all(|player| self.ready.contains(&player.id))
+    }
+
+    pub fn lobby_players(&self) -> Vec<LobbyPlayer> {
+        let host_id = &self.get_host().id;
+        self.seats
+            .iter()
+            .map(|player| LobbyPlayer {
+                id: player.get_id(),
+                name: player.get_name().to_owned(),
+                is_ready: self.ready.contains(&player.id),
+                is_host: &player.id == host_id,
+            })
+            .collect()
+    }
+
+    /// Checks that `player_id` may select `coordinates` right now.
+    pub fn validate_selection(&self, player_id: &str, coordinates: Point) -> Result<(), MoveRejection> {
+        if !self.is_running {
+            return Err(MoveRejection::GameNotStarted);
+        }
+        if self.is_over() {
+            return Err(MoveRejection::GameOver);
+        }
+        if !self.is_player_active(player_id) {
+            return Err(MoveRejection::NotYourTurn);
+        }
+        match self.get_board().cell_at(coordinates) {
+            None => Err(MoveRejection::OutOfBounds),
+            Some(cell) if cell.cleared => Err(MoveRejection::AlreadyCleared),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Checks that `coordinates` can be marked right now. Unlike selections, marks don't wait for the player's turn.
+    pub fn validate_mark(&self, coordinates: Point) -> Result<(), MoveRejection> {
+        if !self.is_running {
+            return Err(MoveRejection::GameNotStarted);
+        }
+        if self.is_over() {
+            return Err(MoveRejection::GameOver);
+        }
+        match self.get_board().cell_at(coordinates) {
+            None => Err(MoveRejection::OutOfBounds),
+            Some(cell) if cell.cleared => Err(MoveRejection::AlreadyCleared),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Finding a mine scores a point and keeps the turn, anything else passes it to the next seat.
+    pub fn player_selected(&mut self, player_id: &str, coordinates: Point) {
+        let found_mine = self.get_board().cell_at(coordinates).is_some_and(|cell| cell.is_mine());
         self.multi_game.player_selected(coordinates);
+        // Selections can clear whole areas, marks on them aren't needed anymore.
+        let board = self.multi_game.get_board();
+        self.marks.retain(|(marked, _, _)| board.cell_at(*marked).is_some_and(|cell| !cell.cleared));
+        if let Some(player) = self.player_with_id(player_id) {
+            let record = MoveRecord {
+                player_id: player.get_id(),
+                player: player.get_name().to_owned(),
+                coordinates: coordinates.into(),
+                elapsed_ms: self.started_at.elapsed().as_millis() as u64,
+                found_mine,
+            };
+            self.moves.push(record);
+        }
+        if found_mine {
+            *self.scores.entry(player_id.to_owned()).or_default() += 1;
+            self.turn_started_at = Instant::now();
+        } else {
+            self.pass_turn();
+        }
+    }
+
+    /// Puts `mark` on the cell at `coordinates` for `player_id`, or takes the cell's mark off without one.
+    pub fn set_mark(&mut self, coordinates: Point, mark: Option<CellMark>, player_id: String) {
+        self.marks.retain(|(marked, _, _)| *marked != coordinates);
+        if let Some(mark) = mark {
+            self.marks.push((coordinates, mark, player_id));
+        }
+    }
+
+    pub fn marks(&self) -> Vec<MarkedCell> {
+        self.marks
+            .iter()
+            .map(|(coordinates, mark, player_id)| MarkedCell {
+                coordinates: (*coordinates).into(),
+                mark: *mark,
+                player_id: player_id.clone(),
+            })
+            .collect()
+    }
+
+    /// Gives the turn to the next seat still in the match and restarts the clock.
+    fn pass_turn(&mut self) {
+        self.turn = (self.turn + 1) % self.seats.len();
+        self.skip_forfeited();
+        self.turn_started_at = Instant::now();
+    }
+
+    /// Moves the turn on from the current seat to the first player who didn't forfeit.
+    fn skip_forfeited(&mut self) {
+        for _ in 0..self.seats.len() {
+            if !self.forfeited.contains(&self.seats[self.turn].id) {
+                break;
+            }
+            self.turn = (self.turn + 1) % self.seats.len();
+        }
+    }
+
+    /// Counts the finished match for its winner in the series score.
+    pub fn credit_winner(&mut self) {
+        if let Some(winner_id) = self.winner().map(|winner| winner.get_id()) {
+            *self.wins.entry(winner_id).or_default() += 1;
+        }
+    }
+
+    pub fn turn_elapsed(&self) -> Duration {
+        self.turn_started_at.elapsed()
+    }
+
+    pub fn active_player(&self) -> Option<&Player> {
+        self.seats.get(self.turn)
+    }
+
+    /// The active player ran out of time: their turn passes, and after `max_timeouts` they forfeit the match.
+    /// Returns the name of that player and whether they forfeited.
+    pub fn time_out(&mut self, max_timeouts: u32) -> Option<(String, bool)> {
+        let player = self.seats.get(self.turn)?;
+        let (player_id, name) = (player.get_id(), player.get_name().to_owned());
+        let timeouts = self.timeouts.entry(player_id.clone()).or_default();
+        *timeouts += 1;
+        let has_forfeited = *timeouts >= max_timeouts;
+        if has_forfeited {
+            self.forfeited.insert(player_id);
+        }
+        self.pass_turn();
+        Some((name, has_forfeited))
+    }
+
+    pub fn request_rematch(&mut self, player_id: String) {
+        self.rematch.insert(player_id);
+    }
+
+    /// Whether every seated player asked for a rematch of the finished game.
+    pub fn is_rematch_agreed(&self) -> bool {
+        self.is_over() && self.seats.len() >= 2 && self.seats.iter().all(|player| self.rematch.contains(&player.id))
+    }
+
+    pub fn rematch_requests(&self) -> Vec<String> {
+        self.seats.iter().filter(|player| self.rematch.contains(&player.id)).map(|player| player.get_name().to_owned()).collect()
+    }
+
+    pub fn score_of(&self, player: &Player) -> u32 {
+        self.scores.get(&player.id).copied().unwrap_or_default()
+    }
+
+    pub fn remaining_mines(&self) -> u32 {
+        self.multi_game.game.remaining_mines()
+    }
+
+    /// The player nobody can catch up with anymore, or the last one who didn't forfeit, if any.
+    pub fn winner(&self) -> Option<&Player> {
+        if !self.is_running {
+            return None;
+        }
+        let mut standings: Vec<(&Player, u32)> = self
+            .seats
+            .iter()
+            .filter(|player| !self.forfeited.contains(&player.id))
+            .map(|player| (player, self.score_of(player)))
+            .collect();
+        standings.sort_by_key(|(_, score)| Reverse(*score));
+        match standings.as_slice() {
+            [(last_player, _)] => Some(*last_player),
+            [(leader, lead), (_, second), ..] if *lead > second + self.remaining_mines() => Some(*leader),
+            _ => None,
+        }
+    }
+
+    /// A game is over once it has a winner or every mine has been found.
+    pub fn is_over(&self) -> bool {
+        self.winner().is_some() || self.remaining_mines() == 0
+    }
+
+    /// The current match as saved once it's over.
+    pub fn record(&self) -> GameRecord {
+        GameRecord {
+            game_id: self.get_id(),
+            difficulty: self.difficulty,
+            players: self.seats.iter().map(|player| (player.get_name().to_owned(), self.score_of(player))).collect(),
+            bots: self.seats.iter().filter(|player| player.is_bot).map(|player| player.get_name().to_owned()).collect(),
+            winner: self.winner().map(|winner| winner.get_name().to_owned()),
+            duration: self.started_at.elapsed(),
+            moves: self.moves.clone(),
+        }
+    }
+
+    pub fn replay(&self) -> Replay {
+        Replay {
+            difficulty: self.difficulty,
+            board: self.initial_board.clone().into(),
+            players: self.seats.iter().map(|player| player.get_name().to_owned()).collect(),
+            moves: self.moves.clone(),
+            seed: Some(self.seed),
+        }
+    }
+
+    pub fn player_summaries(&self) -> Vec<PlayerSummary> {
+        self.seats
+            .iter()
+            .map(|player| PlayerSummary {
+                id: player.get_id(),
+                name: player.get_name().to_owned(),
+                score: self.score_of(player),
+                wins: self.wins.get(&player.id).copied().unwrap_or_default(),
+                has_forfeited: self.forfeited.contains(&player.id),
+                is_active: self.is_running && self.is_player_active(player.get_id()),
+            })
+            .collect()
     }
 
     pub fn is_player_active(&self, player_id: impl Into<String>) -> bool {
-        self.multi_game.current_player().id == player_id.into()
+        self.seats.get(self.turn).is_some_and(|player| player.id == player_id.into())
+    }
+
+    /// Moves the seat of the player with `old_client_id` to their new connection.
+    pub fn reconnect_player(&mut self, old_client_id: ClientId, client: ClientHandle) -> Option<&Player> {
+        let player = self.seats.iter_mut().find(|player| player.get_client_id() == old_client_id)?;
+        player.client = client;
+        Some(player)
+    }
+
+    pub fn player_at(&self, client_id: ClientId) -> Option<&Player> {
+        self.seats.iter().find(|player| player.get_client_id() == client_id)
+    }
+
+    /// The seated player or spectator with `client_id`.
+    pub fn participant_at(&self, client_id: ClientId) -> Option<&Player> {
+        self.seats.iter().chain(self.spectators.iter()).find(|participant| participant.get_client_id() == client_id)
+    }
+
+    pub fn player_with_id(&self, player_id: &str) -> Option<&Player> {
+        self.seats.iter().find(|player| player.id == player_id)
     }
 
     pub fn get_players(&self) -> Vec<&Player> {
-        let mut players = vec![&self.host];
-        if let Some(client) = &self.client {
-            players.push(client);
+        self.seats.iter().collect()
+    }
+
+    /// Players and spectators: everyone who follows the game's moves.
+    pub fn get_participants(&self) -> Vec<&Player> {
+        let mut participants = self.get_players();
+        participants.extend(self.spectators.iter());
+        participants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc::unbounded;
+
+    fn client() -> ClientHandle {
+        let (tx, _) = unbounded();
+        let (events, _) = unbounded();
+        ClientHandle {
+            id: ClientId::next(),
+            tx,
+            events,
+            capabilities: vec![],
         }
-        players
+    }
+
+    /// A running game of `player_count` players on a board with 5 mines.
+    fn game_of(player_count: u16) -> Game {
+        let difficulty = GameDifficulty::Custom { width: 5, height: 5, mines: 5 };
+        let host = Player::new("player 0".to_owned(), client());
+        let mut game = Game::new(host, "game", "game".to_owned(), difficulty, player_count as usize, Some(1), BoardGeneration::Classic);
+        for seat in 1..player_count {
+            game.add_player(Player::new(format!("player {}", seat), client()));
+        }
+        game.start();
+        game
+    }
+
+    fn set_scores(game: &mut Game, scores: &[u32]) {
+        for (player, score) in game.seats.iter().zip(scores) {
+            game.scores.insert(player.get_id(), *score);
+        }
+    }
+
+    fn forfeit(game: &mut Game, seat: usize) {
+        let player_id = game.seats[seat].get_id();
+        game.forfeited.insert(player_id);
+    }
+
+    fn find_every_mine(game: &mut Game) {
+        let mut mines = vec![];
+        game.get_board().for_each_cell(|point, cell, _| {
+            if cell.is_mine() {
+                mines.push(point);
+            }
+        });
+        for mine in mines {
+            game.multi_game.player_selected(mine);
+        }
+    }
+
+    fn winner_name(game: &Game) -> Option<&str> {
+        game.winner().map(|winner| winner.get_name())
+    }
+
+    #[test]
+    fn leader_wins_once_nobody_can_catch_up() {
+        let mut game = game_of(4);
+        set_scores(&mut game, &[6, 1, 0, 1]);
+        assert_eq!(winner_name(&game), None);
+        assert!(!game.is_over());
+
+        set_scores(&mut game, &[7, 1, 0, 1]);
+        assert_eq!(winner_name(&game), Some("player 0"));
+        assert!(game.is_over());
+    }
+
+    #[test]
+    fn only_the_runner_up_decides_the_lead() {
+        let mut game = game_of(3);
+        set_scores(&mut game, &[2, 8, 2]);
+        assert_eq!(winner_name(&game), Some("player 1"));
+
+        set_scores(&mut game, &[2, 8, 4]);
+        assert_eq!(winner_name(&game), None);
+    }
+
+    #[test]
+    fn tie_for_the_lead_ends_without_a_winner() {
+        let mut game = game_of(3);
+        find_every_mine(&mut game);
+        set_scores(&mut game, &[2, 2, 1]);
+        assert_eq!(game.remaining_mines(), 0);
+        assert_eq!(winner_name(&game), None);
+        assert!(game.is_over());
+    }
+
+    #[test]
+    fn forfeited_players_can_not_win() {
+        let mut game = game_of(3);
+        set_scores(&mut game, &[9, 1, 0]);
+        forfeit(&mut game, 0);
+        assert_eq!(winner_name(&game), None);
+
+        forfeit(&mut game, 1);
+        assert_eq!(winner_name(&game), Some("player 2"));
+        assert!(game.is_over());
+    }
+
+    fn active_name(game: &Game) -> Option<&str> {
+        game.active_player().map(|player| player.get_name())
+    }
+
+    #[test]
+    fn turn_passes_over_forfeited_players() {
+        let mut game = game_of(4);
+        forfeit(&mut game, 1);
+        forfeit(&mut game, 2);
+        game.pass_turn();
+        assert_eq!(active_name(&game), Some("player 3"));
+        game.pass_turn();
+        assert_eq!(active_name(&game), Some("player 0"));
+    }
+
+    #[test]
+    fn active_player_leaving_passes_the_turn_over_forfeited_players() {
+        let mut game = game_of(4);
+        game.turn = 1;
+        forfeit(&mut game, 2);
+        let leaving = game.seats[1].get_client_id();
+        game.remove_player(leaving);
+        assert_eq!(active_name(&game), Some("player 3"));
+    }
+
+    #[test]
+    fn last_seat_leaving_passes_the_turn_over_forfeited_players() {
+        let mut game = game_of(4);
+        game.turn = 3;
+        forfeit(&mut game, 0);
+        let leaving = game.seats[3].get_client_id();
+        game.remove_player(leaving);
+        assert_eq!(active_name(&game), Some("player 1"));
+    }
+
+    #[test]
+    fn turn_stays_with_the_active_player_when_someone_else_leaves() {
+        let mut game = game_of(4);
+        game.turn = 2;
+        let leaving = game.seats[1].get_client_id();
+        game.remove_player(leaving);
+        assert_eq!(active_name(&game), Some("player 2"));
+    }
+
+    #[test]
+    fn next_seat_hosts_once_the_host_leaves() {
+        let mut game = game_of(3);
+        let host = game.get_host().get_client_id();
+        game.remove_player(host);
+        assert_eq!(game.get_host().get_name(), "player 1");
+        assert!(game.is_running());
+        assert!(game.lobby_players()[0].is_host);
+    }
+
+    #[test]
+    fn marks_go_away_with_the_cells_they_are_on() {
+        let mut game = game_of(2);
+        let mut safe_cells = vec![];
+        game.get_board().for_each_cell(|point, cell, _| {
+            if !cell.is_mine() {
+                safe_cells.push(point);
+            }
+        });
+        let player_id = game.seats[0].get_id();
+        game.set_mark(safe_cells[0], Some(CellMark::Flag), player_id.clone());
+        game.set_mark(safe_cells[1], Some(CellMark::Question), player_id.clone());
+        game.set_mark(safe_cells[1], None, player_id.clone());
+        assert_eq!(game.marks().len(), 1);
+
+        game.player_selected(&player_id, safe_cells[0]);
+        assert!(game.marks().is_empty());
+    }
+
+    #[test]
+    fn game_in_the_lobby_has_no_winner() {
+        let mut game = game_of(3);
+        game.stop();
+        set_scores(&mut game, &[9, 0, 0]);
+        assert_eq!(winner_name(&game), None);
     }
 }
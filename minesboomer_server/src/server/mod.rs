@@ -1,187 +1,628 @@
+mod actor;
+mod bot;
+mod chat;
+mod dealer;
+mod error;
 mod game;
+mod solver;
+use actor::*;
+use chat::*;
+use error::*;
 use game::*;
 
+pub use actor::GameRegistry;
+
+use crate::config::ServerConfig;
+use crate::store::Store;
+
 use std::{
     collections::HashMap,
+    fmt,
     net::SocketAddr,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, Instant},
 };
 
-use minesweeper_multiplayer::messages::*;
-use minesweeper_multiplayer::serializables::*;
+use minesboomer_protocol::{check_version, shared_capabilities, BoardGeneration, ClientMessage, GameDifficulty, ServerMessage, CAPABILITIES, LATENCY, MAX_CHAT_LENGTH, PROTOCOL_VERSION, SEAT_RANGE};
 use uuid::Uuid;
 
-use futures::channel::mpsc::{unbounded, UnboundedSender};
-use futures_util::{future, pin_mut, stream::TryStreamExt, StreamExt};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures_util::{future, pin_mut, SinkExt, Stream, StreamExt};
 
-use tokio::net::TcpStream;
-use tokio_tungstenite::tungstenite::Message;
+use tokio::{net::TcpStream, sync::oneshot};
+use tokio_tungstenite::tungstenite::{self, Message};
 
 pub type Tx = UnboundedSender<Message>;
-pub type PeerMap = Arc<Mutex<HashMap<SocketAddr, Tx>>>;
-pub type MultiGames = Arc<Mutex<Vec<Game>>>;
-pub type Players = Arc<Mutex<HashMap<SocketAddr, String>>>;
+pub type Sessions = Arc<Mutex<HashMap<String, Session>>>;
+
+/// Liveness of a single connection, updated by every frame the client sends.
+struct Heartbeat {
+    last_seen: Instant,
+    ping_sent_at: Option<Instant>,
+}
+
+/// A client's identity across connections, keyed by the token handed out in `Welcome`.
+pub struct Session {
+    /// The connection currently holding the session.
+    client: ClientHandle,
+    /// Set while the client is away and its seat in that game is held for it.
+    held_game: Option<String>,
+}
+
+/// What a game tells a connection about its membership.
+pub enum ClientEvent {
+    /// The client is not in `game_id` anymore: it was kicked or the game closed.
+    LeftGame { game_id: String },
+    /// The client connected again with the same session, this connection has to make way.
+    /// It replies with the game it was in, for the new connection to take its place there.
+    Replaced { reply: oneshot::Sender<Option<String>> },
+}
+
+/// Tells clients apart within games, bots included, for as long as they are around.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ClientId(u64);
+
+impl ClientId {
+    pub fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        ClientId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl fmt::Display for ClientId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "client {}", self.0)
+    }
+}
+
+/// The way for games to reach a connected client.
+#[derive(Clone)]
+pub struct ClientHandle {
+    pub id: ClientId,
+    tx: Tx,
+    events: UnboundedSender<ClientEvent>,
+    /// Capabilities both sides support, known once the client identified.
+    capabilities: Vec<String>,
+}
+
+impl ClientHandle {
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|supported| supported == capability)
+    }
+
+    pub fn send(&self, message: ServerMessage) -> Result<(), ServerError> {
+        self.send_raw(Message::Text(message.to_json_string()))
+    }
+
+    pub fn notify_event(&self, event: ClientEvent) {
+        // The connection may be gone already, in which case there's nobody left to tell.
+        let _ = self.events.unbounded_send(event);
+    }
+
+    fn close(&self) -> Result<(), ServerError> {
+        self.send_raw(Message::Close(None))
+    }
+
+    fn send_raw(&self, message: Message) -> Result<(), ServerError> {
+        self.tx.unbounded_send(message).map_err(|_| ServerError::ConnectionClosed(self.id))
+    }
+}
+
+/// State owned by a single connection task.
+struct Connection {
+    client: ClientHandle,
+    /// Where the connection comes from, for the logs.
+    address: SocketAddr,
+    /// Set once the client identified.
+    session_token: Option<String>,
+    /// Nickname the client identified with, used in every game it joins.
+    name: String,
+    game: Option<(String, GameHandle)>,
+    chat_limiter: ChatLimiter,
+    heartbeat: Heartbeat,
+}
+
+impl Connection {
+    fn new(client: ClientHandle, address: SocketAddr) -> Self {
+        Connection {
+            client,
+            address,
+            session_token: None,
+            name: "".to_owned(),
+            game: None,
+            chat_limiter: ChatLimiter::default(),
+            heartbeat: Heartbeat { last_seen: Instant::now(), ping_sent_at: None },
+        }
+    }
+
+    fn ensure_not_in_game(&self) -> Result<(), ServerError> {
+        match self.game {
+            Some(_) => Err(ServerError::AlreadyInGame),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends the command built by `command` to the client's game and waits for its answer.
+    async fn request(&self, command: impl FnOnce(ClientId, Reply) -> GameCommand) -> Result<(), ServerError> {
+        let (game_id, game) = self.game.as_ref().ok_or(ServerError::NotInGame)?;
+        let client_id = self.client.id;
+        game.request(game_id, |reply| command(client_id, reply)).await
+    }
+
+    fn handle_event(&mut self, event: ClientEvent) {
+        match event {
+            ClientEvent::LeftGame { game_id } => {
+                if self.game.as_ref().is_some_and(|(current_id, _)| *current_id == game_id) {
+                    self.game = None;
+                }
+            }
+            ClientEvent::Replaced { reply } => {
+                println!("-> {} connected again elsewhere, closing this connection", self.address);
+                // Without its token the connection neither holds a seat nor ends the session once it closes.
+                self.session_token = None;
+                let _ = reply.send(self.game.take().map(|(game_id, _)| game_id));
+                let reason = "You connected again from somewhere else".to_owned();
+                let _ = self.client.send(ServerMessage::Error { reason });
+                let _ = self.client.close();
+            }
+        }
+    }
+}
 
 pub struct Server {
-    peer_map: PeerMap,
-    games: MultiGames,
-    players: Players,
+    games: GameRegistry,
+    sessions: Sessions,
+    connections: AtomicUsize,
+    store: Arc<Mutex<Store>>,
+    config: ServerConfig,
 }
 
 impl Server {
-    pub fn new(peer_map: PeerMap, games: MultiGames, players: Players) -> Self {
-        Server { peer_map, games, players }
+    pub fn new(games: GameRegistry, sessions: Sessions, store: Store, config: ServerConfig) -> Self {
+        Server {
+            games,
+            sessions,
+            connections: AtomicUsize::new(0),
+            store: Arc::new(Mutex::new(store)),
+            config,
+        }
     }
 
     pub async fn handle_connection(self: Arc<Self>, raw_stream: TcpStream, addr: SocketAddr) {
         println!("Incoming TCP connection from: {}", addr);
 
-        let ws_stream = tokio_tungstenite::accept_async(raw_stream).await.expect("Error during the websocket handshake occurred");
+        let mut ws_stream = match tokio_tungstenite::accept_async(raw_stream).await {
+            Ok(ws_stream) => ws_stream,
+            Err(err) => {
+                println!("Error during the websocket handshake with {}: {}", addr, err);
+                return;
+            }
+        };
         println!("WebSocket connection established: {}", addr);
 
+        if self.connections.fetch_add(1, Ordering::SeqCst) >= self.config.max_connections {
+            self.connections.fetch_sub(1, Ordering::SeqCst);
+            println!("-> Refusing {}: connection limit reached", addr);
+            let reason = "The server is full, try again later".to_owned();
+            let _ = ws_stream.send(Message::Text(ServerMessage::Error { reason }.to_json_string())).await;
+            let _ = ws_stream.close(None).await;
+            return;
+        }
+
         let (tx, rx) = unbounded();
-        self.peer_map.lock().unwrap().insert(addr, tx);
+        let (events, events_rx) = unbounded();
+        let client = ClientHandle {
+            id: ClientId::next(),
+            tx,
+            events,
+            capabilities: vec![],
+        };
+        let mut connection = Connection::new(client, addr);
 
-        self.request_identification(addr);
+        self.request_identification(&connection.client);
 
         let (outgoing, incoming) = ws_stream.split();
-
-        let handle_received = incoming.try_for_each(|msg| {
-            println!("Received a message from {}: {}", addr, msg.to_text().unwrap());
-            self.handle_received_message(msg, addr);
-            future::ok(())
-        });
-
-        let receive_from_others = rx.map(Ok).forward(outgoing);
-
-        pin_mut!(handle_received, receive_from_others);
-        future::select(handle_received, receive_from_others).await;
+        {
+            let receive_from_others = rx.map(Ok).forward(outgoing);
+            let serve_client = self.serve(&mut connection, incoming, events_rx);
+            pin_mut!(receive_from_others, serve_client);
+            future::select(receive_from_others, serve_client).await;
+        }
 
         println!("{} disconnected", &addr);
-        self.remove_player(&addr);
+        self.connections.fetch_sub(1, Ordering::SeqCst);
+        self.disconnect(connection).await;
     }
 
-    fn remove_player(&self, addr: &SocketAddr) {
-        let mut games = self.games.lock().unwrap();
-        if let Some(index) = games.iter().position(|game| game.get_host().get_address() == *addr) {
-            let removed_game = games.remove(index);
-            if let Some(client) = removed_game.get_client() {
-                self.send_message_to(client, SimpleMessage::new("host_disconnected").to_json_string());
-            }
-        } else {
-            let mut games_with_clients = games.iter_mut().filter(|game| game.has_client());
-            if let Some(game) = games_with_clients.find(|game| game.get_client().unwrap().get_address() == *addr) {
-                game.remove_client();
-                self.send_message_to(game.get_host(), SimpleMessage::new("client_disconnected").to_json_string());
+    /// Handles the client's frames and the events its games send, one at a time, until it goes away.
+    async fn serve(&self, connection: &mut Connection, mut incoming: impl Stream<Item = Result<Message, tungstenite::Error>> + Unpin, mut events: UnboundedReceiver<ClientEvent>) {
+        let is_pinging = self.config.heartbeat_secs > 0;
+        let mut heartbeat = tokio::time::interval(Duration::from_secs(self.config.heartbeat_secs.max(1)));
+        loop {
+            tokio::select! {
+                frame = incoming.next() => match frame {
+                    Some(Ok(msg)) => self.handle_received_message(msg, connection).await,
+                    _ => return,
+                },
+                Some(event) = events.next() => connection.handle_event(event),
+                _ = heartbeat.tick(), if is_pinging => {
+                    if !self.keep_alive(connection) {
+                        return;
+                    }
+                }
             }
         }
-        self.players.lock().unwrap().remove(addr);
-        self.peer_map.lock().unwrap().remove(addr);
     }
 
-    fn request_identification(&self, addr: SocketAddr) {
-        let message = Message::Text(SimpleMessage::new("identify").to_json_string());
-        let peer_guard = self.peer_map.lock().unwrap();
-        let tx = peer_guard.get(&addr);
+    /// Pings the client, returning false once it has been silent for too long,
+    /// so half-open connections get cleaned up like closed ones.
+    fn keep_alive(&self, connection: &mut Connection) -> bool {
+        let idle_timeout = Duration::from_secs(self.config.idle_timeout_secs);
+        if connection.heartbeat.last_seen.elapsed() >= idle_timeout {
+            println!("{} didn't answer for {}s, dropping it", connection.address, self.config.idle_timeout_secs);
+            return false;
+        }
+        connection.heartbeat.ping_sent_at = Some(Instant::now());
+        connection.client.send_raw(Message::Ping(vec![])).is_ok()
+    }
+
+    /// Takes the client out of its game, unless its seat is held for a reconnection,
+    /// in which case the session is kept until the grace period is over.
+    async fn disconnect(&self, connection: Connection) {
+        let client_id = connection.client.id;
+        let Some(token) = connection.session_token else {
+            return;
+        };
+        let held_game = match connection.game {
+            Some((game_id, game)) if game.hold_seat(client_id).await => Some(game_id),
+            _ => None,
+        };
+        let Some(game_id) = held_game else {
+            lock(&self.sessions).remove(&token);
+            return;
+        };
+        // A new connection may have taken the session over while the seat was being held.
+        if let Some(session) = lock(&self.sessions).get_mut(&token).filter(|session| session.client.id == client_id) {
+            session.held_game = Some(game_id);
+        }
+        let sessions = Arc::clone(&self.sessions);
+        let grace = Duration::from_secs(self.config.reconnect_grace_secs);
+        tokio::spawn(async move {
+            tokio::time::sleep(grace).await;
+            let mut sessions = lock(&sessions);
+            let is_still_away = sessions.get(&token).is_some_and(|session| session.held_game.is_some() && session.client.id == client_id);
+            if is_still_away {
+                sessions.remove(&token);
+            }
+        });
+    }
+
+    fn request_identification(&self, client: &ClientHandle) {
+        let identify = ServerMessage::Identify {
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: CAPABILITIES.iter().map(|capability| capability.to_string()).collect(),
+        };
         println!("-> Sending identify");
-        match tx.unwrap().unbounded_send(message) {
+        match client.send(identify) {
             Ok(_) => println!("Ok."),
             Err(err) => println!("{}", err),
         }
     }
 
-    fn handle_identification_message(&self, message: IdentificationMessage, addr: SocketAddr) {
-        let player = Player::new(message.name, "", addr);
-        self.players.lock().unwrap().insert(addr, player.game_id());
-    }
-
-    fn handle_received_message(&self, msg: Message, addr: SocketAddr) {
-        let message_string = msg.to_text().unwrap();
-        if let Ok(message) = IdentificationMessage::new_from_json(message_string) {
-            println!("Identification received for {}", message.name);
-            self.handle_identification_message(message, addr);
-        } else if let Ok(message) = CellSelectedMessage::new_from_json(message_string) {
-            let game_id = self.players.lock().unwrap().get(&addr).unwrap().clone();
-            let mut games = self.games.lock().unwrap();
-            let game = games.iter_mut().find(|game| game.get_id() == game_id).unwrap();
-            game.player_selected(message.coordinates.into());
-            self.send_selected_to_players(game, message.coordinates);
-        } else if let Ok(message) = CreateGameMessage::new_from_json(message_string) {
-            let mut games_guard = self.games.lock().unwrap();
-            let game_id = Uuid::new_v4().to_string();
-            let player = Player::new(message.game.name, &game_id, addr);
-            self.players.lock().unwrap().insert(addr, player.game_id());
-            let game = Game::new(player, game_id);
-            games_guard.push(game);
-            self.send_message_to_addr(&addr, SimpleMessage::new("waiting_enemy").to_json_string());
-        } else if let Ok(message) = JoinGameMessage::new_from_json(message_string) {
-            println!("-> Client joined game");
-            let mut games = self.games.lock().unwrap();
-            let game = games.iter_mut().find(|game| game.get_id() == message.game_id).unwrap();
-            let client = Player::new(message.client_name, game.get_id(), addr);
-            self.players.lock().unwrap().insert(addr, client.game_id());
-            game.set_client(client);
-            game.generate_multi_game();
-            self.send_new_game_to_players(game);
-        } else if let Ok(message) = SimpleMessage::new_from_json(message_string) {
-            if message.name == "games_request" {
-                self.send_open_games(addr);
+    async fn handle_identification_message(
+        &self,
+        name: String,
+        protocol_version: u32,
+        capabilities: Vec<String>,
+        session_token: Option<String>,
+        connection: &mut Connection,
+    ) -> Result<(), ServerError> {
+        let addr = connection.address;
+        println!("Identification received for {} (protocol v{})", name, protocol_version);
+        // A connection holds a single session. Another one would leave the first behind,
+        // and its own token would have the connection wait on itself to make way.
+        if connection.session_token.is_some() {
+            return Err(ServerError::AlreadyIdentified);
+        }
+        if let Err(reason) = check_version(protocol_version) {
+            println!("-> Rejecting {}: protocol v{} is {:?}", addr, protocol_version, reason);
+            let server_version = PROTOCOL_VERSION;
+            connection.client.send(ServerMessage::IdentifyRejected { reason, server_version })?;
+            return connection.client.close();
+        }
+        let capabilities = shared_capabilities(&capabilities);
+        connection.client.capabilities = capabilities.clone();
+        connection.name = name;
+
+        if let Some((session_token, old_client, held_game)) = session_token.and_then(|token| self.reclaim_session(token, &connection.client)) {
+            let old_client_id = old_client.id;
+            println!("-> Resuming session of {} (was {})", addr, old_client_id);
+            connection.session_token = Some(session_token.clone());
+            let game_id = match held_game {
+                Some(game_id) => Some(game_id),
+                None => Self::take_over(&old_client).await,
+            };
+            if let Some(game_id) = game_id {
+                let welcome = ServerMessage::Welcome {
+                    capabilities: capabilities.clone(),
+                    session_token: session_token.clone(),
+                    resumed: true,
+                };
+                match self.resume_game(old_client_id, game_id.clone(), welcome, connection).await {
+                    Ok(()) => return Ok(()),
+                    // The seat was given up in the meantime: carry on as a fresh client.
+                    Err(err) => {
+                        println!("Unable to resume the game of {}: {}", addr, err);
+                        if let Ok(game) = self.find_game(&game_id) {
+                            game.send(GameCommand::Leave { client_id: old_client_id });
+                        }
+                    }
+                }
             }
+            return connection.client.send(ServerMessage::Welcome {
+                capabilities,
+                session_token,
+                resumed: false,
+            });
         }
+
+        let session_token = Uuid::new_v4().to_string();
+        lock(&self.sessions).insert(session_token.clone(), Session { client: connection.client.clone(), held_game: None });
+        connection.session_token = Some(session_token.clone());
+        connection.client.send(ServerMessage::Welcome {
+            capabilities,
+            session_token,
+            resumed: false,
+        })
     }
 
-    fn send_open_games(&self, addr: SocketAddr) {
-        let games = self.games.lock().unwrap();
-        let game_defs = games
-            .iter()
-            .filter(|game| !game.has_client())
-            .map(|game| {
-                let name = game.get_host().get_name();
-                let id = game.get_id();
-                let difficulty = game.get_difficulty();
-                GameDefinition::new(id, name, difficulty.clone())
-            })
-            .collect();
-        let message = OpenGamesMessage::new(game_defs);
-        let peers = self.peer_map.lock().unwrap();
-        let sender = peers.get(&addr).unwrap();
-        println!("-> Sending OpenGamesMessage: {}", message.to_json_string());
-        sender.unbounded_send(Message::Text(message.to_json_string())).unwrap();
+    /// Binds the session of `token` to `client`, returning its token, previous connection and held game.
+    /// The previous connection may still be open, if the server hasn't noticed the drop yet.
+    fn reclaim_session(&self, token: String, client: &ClientHandle) -> Option<(String, ClientHandle, Option<String>)> {
+        let mut sessions = lock(&self.sessions);
+        let session = sessions.get_mut(&token)?;
+        let held_game = session.held_game.take();
+        let old_client = std::mem::replace(&mut session.client, client.clone());
+        Some((token, old_client, held_game))
     }
 
-    fn send_selected_to_players(&self, game: &Game, coordinates: SerializablePoint) {
-        for player in game.get_players() {
-            let is_active = game.is_player_active(player.get_id());
-            self.send_message_to(player, CellSelectedMessage::new(coordinates, is_active).to_json_string());
+    /// Closes the still open connection of a reconnecting client, returning the game it was in.
+    async fn take_over(old_client: &ClientHandle) -> Option<String> {
+        let (reply, response) = oneshot::channel();
+        old_client.notify_event(ClientEvent::Replaced { reply });
+        // Nothing comes back from a connection that closed in the meantime.
+        response.await.ok().flatten()
+    }
+
+    async fn resume_game(&self, old_client_id: ClientId, game_id: String, welcome: ServerMessage, connection: &mut Connection) -> Result<(), ServerError> {
+        let game = self.find_game(&game_id)?;
+        let client = connection.client.clone();
+        game.request(&game_id, |reply| GameCommand::Reconnect {
+            old_client_id,
+            client,
+            welcome,
+            reply,
+        })
+        .await?;
+        connection.game = Some((game_id, game));
+        Ok(())
+    }
+
+    async fn handle_received_message(&self, msg: Message, connection: &mut Connection) {
+        let addr = connection.address;
+        let now = Instant::now();
+        connection.heartbeat.last_seen = now;
+        if let Message::Pong(_) = msg {
+            if let Some(ping_sent_at) = connection.heartbeat.ping_sent_at.take() {
+                let ms = now.duration_since(ping_sent_at).as_millis() as u64;
+                if connection.client.supports(LATENCY) {
+                    if let Err(err) = connection.client.send(ServerMessage::Latency { ms }) {
+                        println!("Unable to report latency to {}: {}", addr, err);
+                    }
+                }
+            }
+        }
+        // Other control frames (ping, close) are answered by tungstenite itself.
+        let Message::Text(message_string) = msg else {
+            return;
+        };
+        println!("Received a message from {}: {}", addr, message_string);
+        let result = match ClientMessage::new_from_json(&message_string) {
+            Ok(message) => self.handle_client_message(message, connection).await,
+            Err(err) => {
+                println!("Unrecognized message from {}: {}", addr, err);
+                let reason = format!("Unrecognized message: {}", err);
+                connection.client.send(ServerMessage::Error { reason })
+            }
+        };
+        if let Err(err) = result {
+            println!("Error handling message from {}: {}", addr, err);
+            // Reporting the error is best effort, a client that hung up meanwhile doesn't need it.
+            let _ = connection.client.send(ServerMessage::Error { reason: err.to_string() });
         }
     }
 
-    fn send_new_game_to_players(&self, game: &Game) {
-        for player in game.get_players() {
-            let is_active = game.is_player_active(player.get_id());
-            let board: SerializableBoard = game.get_board().clone().into();
-            self.send_message_to(player, GameStartMessage::new(board, is_active).to_json_string());
+    async fn handle_client_message(&self, message: ClientMessage, connection: &mut Connection) -> Result<(), ServerError> {
+        if connection.session_token.is_none() && !matches!(message, ClientMessage::Identify { .. }) {
+            return Err(ServerError::NotIdentified);
+        }
+        match message {
+            ClientMessage::Identify {
+                name,
+                protocol_version,
+                capabilities,
+                session_token,
+            } => self.handle_identification_message(name, protocol_version, capabilities, session_token, connection).await,
+            ClientMessage::GamesRequest => self.send_open_games(&connection.client),
+            ClientMessage::CreateGame { name, difficulty, seats, seed, generation } => self.handle_create_game(name, difficulty, seats, seed, generation, connection),
+            ClientMessage::SetReady { is_ready } => connection.request(|client_id, reply| GameCommand::SetReady { client_id, is_ready, reply }).await,
+            ClientMessage::KickPlayer { player_id } => connection.request(|client_id, reply| GameCommand::Kick { client_id, player_id, reply }).await,
+            ClientMessage::AddBot { strength } => connection.request(|client_id, reply| GameCommand::AddBot { client_id, strength, reply }).await,
+            ClientMessage::ChangeDifficulty { difficulty } => {
+                difficulty.validate().map_err(ServerError::InvalidDifficulty)?;
+                connection.request(|client_id, reply| GameCommand::ChangeDifficulty { client_id, difficulty, reply }).await
+            }
+            ClientMessage::JoinGame { game_id } => self.handle_join_game(game_id, connection).await,
+            ClientMessage::SpectateGame { game_id } => self.handle_spectate_game(game_id, connection).await,
+            ClientMessage::CellSelected { coordinates } => connection.request(|client_id, reply| GameCommand::CellSelected { client_id, coordinates, reply }).await,
+            ClientMessage::MarkCell { coordinates, mark } => connection.request(|client_id, reply| GameCommand::MarkCell { client_id, coordinates, mark, reply }).await,
+            ClientMessage::Chat { text } => self.handle_chat(text, connection).await,
+            ClientMessage::RequestRematch => connection.request(|client_id, reply| GameCommand::RequestRematch { client_id, reply }).await,
+            ClientMessage::StatsRequest => self.send_stats(&connection.client).await,
+            ClientMessage::ReplayRequest => connection.request(|client_id, reply| GameCommand::ReplayRequest { client_id, reply }).await,
+            ClientMessage::LeaveGame => {
+                let (_, game) = connection.game.take().ok_or(ServerError::NotInGame)?;
+                game.send(GameCommand::Leave { client_id: connection.client.id });
+                Ok(())
+            }
         }
     }
 
-    fn send_message_to(&self, player: &Player, message_json: String) {
-        self.send_message_to_addr(&player.get_address(), message_json);
+    fn handle_create_game(&self, name: String, difficulty: Option<GameDifficulty>, seats: Option<usize>, seed: Option<u64>, generation: Option<BoardGeneration>, connection: &mut Connection) -> Result<(), ServerError> {
+        let difficulty = difficulty.unwrap_or(self.config.game_defaults.difficulty);
+        difficulty.validate().map_err(ServerError::InvalidDifficulty)?;
+        let seats = seats.unwrap_or(self.config.game_defaults.seats);
+        let generation = generation.unwrap_or(self.config.game_defaults.generation);
+        if !SEAT_RANGE.contains(&seats) {
+            return Err(ServerError::InvalidSeats(seats));
+        }
+        if lock(&self.games).len() >= self.config.max_games {
+            return Err(ServerError::TooManyGames);
+        }
+        connection.ensure_not_in_game()?;
+        let game_id = Uuid::new_v4().to_string();
+        let player = Player::new(connection.name.clone(), connection.client.clone());
+        let game = Game::new(player, game_id.clone(), name, difficulty, seats, seed, generation);
+        let handle = spawn_game(game, Arc::clone(&self.games), Arc::clone(&self.store), self.config.clone());
+        connection.game = Some((game_id, handle));
+        Ok(())
     }
 
-    fn send_message_to_addr(&self, addr: &SocketAddr, message_json: String) {
-        let peers = self.peer_map.lock().unwrap();
-        let sender = peers.get(addr);
-        sender.unwrap().unbounded_send(Message::Text(message_json)).unwrap();
+    async fn handle_join_game(&self, game_id: String, connection: &mut Connection) -> Result<(), ServerError> {
+        connection.ensure_not_in_game()?;
+        let game = self.find_game(&game_id)?;
+        let (name, client) = (connection.name.clone(), connection.client.clone());
+        game.request(&game_id, |reply| GameCommand::Join { name, client, reply }).await?;
+        connection.game = Some((game_id, game));
+        Ok(())
     }
 
-    fn _send_to_all(&self, msg: Message) {
-        let peers = self.peer_map.lock().unwrap();
+    async fn handle_spectate_game(&self, game_id: String, connection: &mut Connection) -> Result<(), ServerError> {
+        connection.ensure_not_in_game()?;
+        let game = self.find_game(&game_id)?;
+        let (name, client) = (connection.name.clone(), connection.client.clone());
+        game.request(&game_id, |reply| GameCommand::Spectate { name, client, reply }).await?;
+        connection.game = Some((game_id, game));
+        Ok(())
+    }
 
-        let broadcast_recipients = peers.iter().map(|(_, ws_sink)| ws_sink);
+    async fn handle_chat(&self, text: String, connection: &mut Connection) -> Result<(), ServerError> {
+        let text = text.trim().to_owned();
+        if text.is_empty() {
+            return Err(ServerError::EmptyChat);
+        }
+        if text.chars().count() > MAX_CHAT_LENGTH {
+            return Err(ServerError::ChatTooLong);
+        }
+        if connection.game.is_none() {
+            return Err(ServerError::NotInGame);
+        }
+        if !connection.chat_limiter.try_send() {
+            return Err(ServerError::ChatRateLimited);
+        }
+        connection.request(|client_id, reply| GameCommand::Chat { client_id, text, reply }).await
+    }
+
+    fn find_game(&self, game_id: &str) -> Result<GameHandle, ServerError> {
+        lock(&self.games).get(game_id).cloned().ok_or_else(|| ServerError::UnknownGame(game_id.to_owned()))
+    }
+
+    fn send_open_games(&self, client: &ClientHandle) -> Result<(), ServerError> {
+        let game_defs = lock(&self.games).values().filter_map(GameHandle::listing).collect();
+        let message = ServerMessage::OpenGames { games: game_defs };
+        println!("-> Sending OpenGames: {}", message.to_json_string());
+        client.send(message)
+    }
+
+    async fn send_stats(&self, client: &ClientHandle) -> Result<(), ServerError> {
+        // Like saving games, reading the database blocks and is kept off the connection's task.
+        let store = Arc::clone(&self.store);
+        let players = tokio::task::spawn_blocking(move || lock(&store).top_players().map_err(|err| err.to_string()))
+            .await
+            .map_err(|err| ServerError::Storage(err.to_string()))?
+            .map_err(ServerError::Storage)?;
+        client.send(ServerMessage::Stats { players })
+    }
+}
+
+/// Locks `mutex`, recovering the data if another task panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
 
-        println!("Sending message to all");
-        for recp in broadcast_recipients {
-            recp.unbounded_send(msg.clone()).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn server() -> Server {
+        let store = Store::open(Path::new(":memory:")).unwrap();
+        Server::new(GameRegistry::default(), Sessions::default(), store, ServerConfig::default())
+    }
+
+    /// A connection along with the receiving ends of its client's channels, kept open like a live connection's.
+    fn connection() -> (Connection, UnboundedReceiver<Message>, UnboundedReceiver<ClientEvent>) {
+        let (tx, frames) = unbounded();
+        let (events, event_receiver) = unbounded();
+        let client = ClientHandle {
+            id: ClientId::next(),
+            tx,
+            events,
+            capabilities: vec![],
+        };
+        (Connection::new(client, "127.0.0.1:1234".parse().unwrap()), frames, event_receiver)
+    }
+
+    fn identify(session_token: Option<String>) -> ClientMessage {
+        ClientMessage::Identify {
+            name: "player".to_owned(),
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: vec![],
+            session_token,
         }
     }
+
+    async fn handle(server: &Server, message: ClientMessage, connection: &mut Connection) -> Result<(), ServerError> {
+        tokio::time::timeout(Duration::from_secs(1), server.handle_client_message(message, connection)).await.expect("the message was never handled")
+    }
+
+    #[tokio::test]
+    async fn second_identify_keeps_the_first_session() {
+        let server = server();
+        let (mut connection, _frames, _events) = connection();
+        handle(&server, identify(None), &mut connection).await.unwrap();
+        let token = connection.session_token.clone();
+
+        let result = handle(&server, identify(None), &mut connection).await;
+        assert!(matches!(result, Err(ServerError::AlreadyIdentified)));
+        assert_eq!(connection.session_token, token);
+        assert_eq!(lock(&server.sessions).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn identify_with_own_token_is_rejected() {
+        let server = server();
+        let (mut connection, _frames, _events) = connection();
+        handle(&server, identify(None), &mut connection).await.unwrap();
+        let token = connection.session_token.clone();
+
+        let result = handle(&server, identify(token.clone()), &mut connection).await;
+        assert!(matches!(result, Err(ServerError::AlreadyIdentified)));
+        assert_eq!(connection.session_token, token);
+        let sessions = lock(&server.sessions);
+        assert!(sessions.get(&token.unwrap()).is_some_and(|session| session.client.id == connection.client.id));
+    }
 }
@@ -0,0 +1,257 @@
+use minesweeper_multiplayer::{Multiplayer, Point};
+
+/// What a player can tell about a cell from the board they are shown.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Seen {
+    Hidden,
+    Number(usize),
+    /// A mine somebody already found.
+    Mine,
+}
+
+/// Works out what can be known about the hidden cells of a board, using only what players see:
+/// the numbers of cleared cells, the mines found so far and how many are left.
+pub struct Solver {
+    width: usize,
+    height: usize,
+    cells: Vec<Seen>,
+    remaining_mines: usize,
+}
+
+impl Solver {
+    pub fn new(game: &Multiplayer) -> Self {
+        let dimentions = game.get_board_dimentions();
+        let (width, height) = (dimentions.width, dimentions.height);
+        let mut cells = Vec::with_capacity(width * height);
+        for x in 0..width {
+            for y in 0..height {
+                let seen = match game.get_board().cell_at(Point { x, y }) {
+                    Some(cell) if cell.cleared && cell.is_mine() => Seen::Mine,
+                    Some(cell) if cell.cleared => Seen::Number(cell.number as usize),
+                    _ => Seen::Hidden,
+                };
+                cells.push(seen);
+            }
+        }
+        Solver {
+            width,
+            height,
+            cells,
+            remaining_mines: game.game.remaining_mines() as usize,
+        }
+    }
+
+    /// Chance of each hidden cell hiding a mine: 1 for the ones that must, 0 for the ones that can't.
+    pub fn mine_chances(&self) -> Vec<(Point, f32)> {
+        let known = self.deduce();
+        let known_mines = known.iter().filter(|known| **known == Some(true)).count();
+        let mut chances: Vec<Option<f32>> = known.iter().map(|known| known.map(|is_mine| if is_mine { 1. } else { 0. })).collect();
+
+        // Cells next to a number are as likely as its most crowded neighbourhood makes them.
+        for index in 0..self.cells.len() {
+            let Seen::Number(number) = self.cells[index] else {
+                continue;
+            };
+            let unknown: Vec<usize> = self.neighbours(index).filter(|neighbour| self.cells[*neighbour] == Seen::Hidden && known[*neighbour].is_none()).collect();
+            if unknown.is_empty() {
+                continue;
+            }
+            let missing = number.saturating_sub(self.mines_around(index, &known));
+            let chance = missing as f32 / unknown.len() as f32;
+            for neighbour in unknown {
+                let previous = chances[neighbour].unwrap_or(0.);
+                chances[neighbour] = Some(previous.max(chance));
+            }
+        }
+
+        // The mines left over are spread evenly over the cells no number tells anything about.
+        let frontier_mines: f32 = (0..self.cells.len()).filter(|index| known[*index].is_none()).filter_map(|index| chances[index]).sum();
+        let unconstrained = (0..self.cells.len()).filter(|index| self.cells[*index] == Seen::Hidden && chances[*index].is_none()).count();
+        let leftover = (self.remaining_mines.saturating_sub(known_mines) as f32 - frontier_mines).max(0.);
+        let density = if unconstrained > 0 { (leftover / unconstrained as f32).min(1.) } else { 0. };
+
+        (0..self.cells.len())
+            .filter(|index| self.cells[*index] == Seen::Hidden)
+            .map(|index| (self.point(index), chances[index].unwrap_or(density)))
+            .collect()
+    }
+
+    /// Hidden cells that are proven to hide a mine (`true`) or to be safe (`false`), leaving out anything only likely.
+    pub fn proven(&self) -> Vec<(Point, bool)> {
+        let mut known = self.deduce();
+        let known_mines = known.iter().filter(|known| **known == Some(true)).count();
+        let unknown: Vec<usize> = (0..self.cells.len()).filter(|index| self.cells[*index] == Seen::Hidden && known[*index].is_none()).collect();
+        // The mine count settles the rest once they all are mines, or none of them is.
+        let missing = self.remaining_mines.saturating_sub(known_mines);
+        if missing == 0 || missing == unknown.len() {
+            for index in unknown {
+                known[index] = Some(missing > 0);
+            }
+        }
+        (0..self.cells.len()).filter_map(|index| known[index].map(|is_mine| (self.point(index), is_mine))).collect()
+    }
+
+    /// Whether every cell of the board was cleared.
+    pub fn is_solved(&self) -> bool {
+        !self.cells.contains(&Seen::Hidden)
+    }
+
+    /// Marks every hidden cell that the numbers around it prove to be a mine (`Some(true)`) or safe (`Some(false)`).
+    fn deduce(&self) -> Vec<Option<bool>> {
+        let mut known = vec![None; self.cells.len()];
+        let mut has_changed = true;
+        while has_changed {
+            has_changed = false;
+            for index in 0..self.cells.len() {
+                let Some((unknown, missing)) = self.constraint(index, &known) else {
+                    continue;
+                };
+                if missing == 0 || missing == unknown.len() {
+                    for neighbour in unknown {
+                        known[neighbour] = Some(missing > 0);
+                    }
+                    has_changed = true;
+                }
+            }
+            if !has_changed {
+                has_changed = self.deduce_from_pairs(&mut known);
+            }
+        }
+        known
+    }
+
+    /// Compares numbers that share hidden cells, which tells more than each of them alone:
+    /// a 1 next to a 2 that sees one more cell puts the 2's other mine on that cell.
+    /// Returns whether anything new was proven.
+    fn deduce_from_pairs(&self, known: &mut [Option<bool>]) -> bool {
+        let mut has_changed = false;
+        for index in 0..self.cells.len() {
+            let Some((unknown, missing)) = self.constraint(index, known) else {
+                continue;
+            };
+            for other in self.around(index, 2).filter(|other| matches!(self.cells[*other], Seen::Number(_))) {
+                let Some((other_unknown, other_missing)) = self.constraint(other, known) else {
+                    continue;
+                };
+                let shared = unknown.iter().filter(|cell| other_unknown.contains(cell)).count();
+                let only_other: Vec<usize> = other_unknown.into_iter().filter(|cell| !unknown.contains(cell)).collect();
+                if shared == 0 || only_other.is_empty() {
+                    continue;
+                }
+                // Mines on the shared cells are bounded by both numbers, the rest of the other number's mines go on its own cells.
+                let most_shared = shared.min(missing).min(other_missing);
+                let least_shared = missing.saturating_sub(unknown.len() - shared);
+                let is_mine = if other_missing.saturating_sub(least_shared) == 0 {
+                    false
+                } else if other_missing - most_shared == only_other.len() {
+                    true
+                } else {
+                    continue;
+                };
+                for cell in only_other {
+                    known[cell] = Some(is_mine);
+                }
+                has_changed = true;
+            }
+        }
+        has_changed
+    }
+
+    /// The hidden cells around the number at `index` that aren't proven yet, with how many mines they still hide.
+    fn constraint(&self, index: usize, known: &[Option<bool>]) -> Option<(Vec<usize>, usize)> {
+        let Seen::Number(number) = self.cells[index] else {
+            return None;
+        };
+        let unknown: Vec<usize> = self.neighbours(index).filter(|neighbour| self.cells[*neighbour] == Seen::Hidden && known[*neighbour].is_none()).collect();
+        if unknown.is_empty() {
+            return None;
+        }
+        Some((unknown, number.saturating_sub(self.mines_around(index, known))))
+    }
+
+    /// Mines around `index` that were found or proven so far.
+    fn mines_around(&self, index: usize, known: &[Option<bool>]) -> usize {
+        self.neighbours(index).filter(|neighbour| self.cells[*neighbour] == Seen::Mine || known[*neighbour] == Some(true)).count()
+    }
+
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.around(index, 1)
+    }
+
+    /// Cells at most `distance` cells away from `index` in both directions, `index` left out.
+    fn around(&self, index: usize, distance: usize) -> impl Iterator<Item = usize> + '_ {
+        let (x, y) = (index / self.height, index % self.height);
+        let xs = x.saturating_sub(distance)..=(x + distance).min(self.width - 1);
+        xs.flat_map(move |nx| {
+            let ys = y.saturating_sub(distance)..=(y + distance).min(self.height - 1);
+            ys.map(move |ny| nx * self.height + ny)
+        })
+        .filter(move |neighbour| *neighbour != index)
+    }
+
+    fn point(&self, index: usize) -> Point {
+        Point {
+            x: index / self.height,
+            y: index % self.height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minesboomer_protocol::GameDifficulty;
+    use minesweeper_multiplayer::{Board, Cell, Size};
+
+    /// A game on a `width` by `height` board with `mines`, where the cells at `cleared` were selected.
+    fn game_with(width: usize, height: usize, mines: &[Point], cleared: &[Point]) -> Multiplayer {
+        let difficulty = GameDifficulty::Custom { width: width as u32, height: height as u32, mines: mines.len() as u32 };
+        let mut game = difficulty.new_game(["", ""]);
+        let mut board = Board::new_empty(Size { width, height });
+        for mine in mines {
+            board.replace_cell(Cell::new_mine(*mine), *mine);
+        }
+        board.add_cell_numbers();
+        game.game.board = board;
+        for point in cleared {
+            game.player_selected(*point);
+        }
+        game
+    }
+
+    fn proven_at(solver: &Solver, point: Point) -> Option<bool> {
+        solver.proven().into_iter().find(|(proven, _)| *proven == point).map(|(_, is_mine)| is_mine)
+    }
+
+    #[test]
+    fn numbers_and_the_mine_count_settle_cells() {
+        // ? ? 1 0 0   the 1 only sees one hidden cell, and the board has no other mine.
+        let mines = [Point { x: 1, y: 0 }];
+        let solver = Solver::new(&game_with(5, 1, &mines, &[Point { x: 4, y: 0 }]));
+        assert_eq!(proven_at(&solver, Point { x: 1, y: 0 }), Some(true));
+        assert_eq!(proven_at(&solver, Point { x: 0, y: 0 }), Some(false));
+    }
+
+    #[test]
+    fn pairs_of_numbers_settle_what_neither_does_alone() {
+        // ? ? ?
+        // 1 2 1   neither number alone places a mine, but the 1 next to the 2 does.
+        let mines = [Point { x: 0, y: 0 }, Point { x: 2, y: 0 }];
+        let cleared = [Point { x: 0, y: 1 }, Point { x: 1, y: 1 }, Point { x: 2, y: 1 }];
+        let solver = Solver::new(&game_with(3, 2, &mines, &cleared));
+        assert_eq!(proven_at(&solver, Point { x: 0, y: 0 }), Some(true));
+        assert_eq!(proven_at(&solver, Point { x: 1, y: 0 }), Some(false));
+        assert_eq!(proven_at(&solver, Point { x: 2, y: 0 }), Some(true));
+    }
+
+    #[test]
+    fn guesses_stay_unproven() {
+        // ? ?
+        // 1 1   the mine is on either side.
+        let mines = [Point { x: 0, y: 0 }];
+        let cleared = [Point { x: 0, y: 1 }, Point { x: 1, y: 1 }];
+        let solver = Solver::new(&game_with(2, 2, &mines, &cleared));
+        assert!(solver.proven().is_empty());
+        assert!(solver.mine_chances().iter().all(|(_, chance)| *chance > 0. && *chance < 1.));
+    }
+}
@@ -0,0 +1,147 @@
+use minesboomer_protocol::{GameDifficulty, MoveRecord, PlayerStats};
+use rusqlite::{params, Connection};
+use std::{
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Number of players returned by `Store::top_players`.
+const LEADERBOARD_SIZE: u32 = 10;
+
+/// A finished game, as saved to the database.
+pub struct GameRecord {
+    pub game_id: String,
+    pub difficulty: GameDifficulty,
+    /// Each player's name with the mines they found.
+    pub players: Vec<(String, u32)>,
+    /// Names of the players who were bots, kept out of the statistics.
+    pub bots: Vec<String>,
+    pub winner: Option<String>,
+    pub duration: Duration,
+    pub moves: Vec<MoveRecord>,
+}
+
+/// Finished games and per-name statistics, kept in a SQLite file across restarts.
+pub struct Store {
+    connection: Connection,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let connection = Connection::open(path).map_err(|err| format!("Unable to open the database {}: {}", path.display(), err))?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS games (
+                    id INTEGER PRIMARY KEY,
+                    game_id TEXT NOT NULL,
+                    finished_at INTEGER NOT NULL,
+                    difficulty TEXT NOT NULL,
+                    players TEXT NOT NULL,
+                    winner TEXT,
+                    duration_secs INTEGER NOT NULL,
+                    moves TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS player_stats (
+                    name TEXT PRIMARY KEY,
+                    games INTEGER NOT NULL DEFAULT 0,
+                    wins INTEGER NOT NULL DEFAULT 0,
+                    losses INTEGER NOT NULL DEFAULT 0,
+                    mines_found INTEGER NOT NULL DEFAULT 0
+                );",
+            )
+            .map_err(|err| format!("Unable to set up the database {}: {}", path.display(), err))?;
+        Ok(Store { connection })
+    }
+
+    /// Saves `record` and adds its results to the statistics of every player who isn't a bot.
+    pub fn record_game(&mut self, record: &GameRecord) -> rusqlite::Result<()> {
+        let finished_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO games (game_id, finished_at, difficulty, players, winner, duration_secs, moves) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                record.game_id,
+                finished_at,
+                to_json(&record.difficulty),
+                to_json(&record.players),
+                record.winner,
+                record.duration.as_secs(),
+                to_json(&record.moves),
+            ],
+        )?;
+        for (name, mines_found) in record.players.iter().filter(|(name, _)| !record.bots.contains(name)) {
+            let is_winner = record.winner.as_ref() == Some(name);
+            let is_loser = record.winner.is_some() && !is_winner;
+            transaction.execute(
+                "INSERT INTO player_stats (name, games, wins, losses, mines_found) VALUES (?1, 1, ?2, ?3, ?4)
+                 ON CONFLICT(name) DO UPDATE SET
+                    games = games + 1,
+                    wins = wins + excluded.wins,
+                    losses = losses + excluded.losses,
+                    mines_found = mines_found + excluded.mines_found",
+                params![name, is_winner as u32, is_loser as u32, mines_found],
+            )?;
+        }
+        transaction.commit()
+    }
+
+    /// The players with the most wins.
+    pub fn top_players(&self) -> rusqlite::Result<Vec<PlayerStats>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT name, games, wins, losses, mines_found FROM player_stats ORDER BY wins DESC, mines_found DESC LIMIT ?1")?;
+        let rows = statement.query_map([LEADERBOARD_SIZE], |row| {
+            Ok(PlayerStats {
+                name: row.get(0)?,
+                games: row.get(1)?,
+                wins: row.get(2)?,
+                losses: row.get(3)?,
+                mines_found: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("Game records are always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(players: &[(&str, u32)], bots: &[&str], winner: &str) -> GameRecord {
+        GameRecord {
+            game_id: "game".to_owned(),
+            difficulty: GameDifficulty::Easy,
+            players: players.iter().map(|(name, mines_found)| (name.to_string(), *mines_found)).collect(),
+            bots: bots.iter().map(|name| name.to_string()).collect(),
+            winner: Some(winner.to_owned()),
+            duration: Duration::from_secs(60),
+            moves: vec![],
+        }
+    }
+
+    #[test]
+    fn stats_add_up_by_name() {
+        let mut store = Store::open(Path::new(":memory:")).unwrap();
+        store.record_game(&record(&[("alice", 7), ("bob", 3)], &[], "alice")).unwrap();
+        store.record_game(&record(&[("bob", 6), ("alice", 4)], &[], "bob")).unwrap();
+        store.record_game(&record(&[("alice", 6), ("carol", 2)], &[], "alice")).unwrap();
+
+        let stats = store.top_players().unwrap();
+        let summary: Vec<(&str, u32, u32, u32, u32)> = stats.iter().map(|stats| (stats.name.as_str(), stats.games, stats.wins, stats.losses, stats.mines_found)).collect();
+        assert_eq!(summary, [("alice", 3, 2, 1, 17), ("bob", 2, 1, 1, 9), ("carol", 1, 0, 1, 2)]);
+    }
+
+    #[test]
+    fn bots_stay_off_the_leaderboard() {
+        let mut store = Store::open(Path::new(":memory:")).unwrap();
+        store.record_game(&record(&[("alice", 3), ("Expert bot", 7)], &["Expert bot"], "Expert bot")).unwrap();
+
+        let stats = store.top_players().unwrap();
+        let summary: Vec<(&str, u32, u32, u32)> = stats.iter().map(|stats| (stats.name.as_str(), stats.games, stats.wins, stats.losses)).collect();
+        assert_eq!(summary, [("alice", 1, 0, 1)]);
+    }
+}